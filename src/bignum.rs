@@ -0,0 +1,675 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// An arbitrary precision integer.  The magnitude is stored as base 2^32
+/// digits, least significant first, with no trailing zero digits.  Zero is
+/// never negative.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+fn trim(digits: &mut Vec<u32>) {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+}
+
+fn cmp_digits(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x.cmp(y);
+        }
+    }
+    Ordering::Equal
+}
+
+fn add_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for (i, &x) in a.iter().enumerate() {
+        let sum = x as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry != 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+/// Computes `a - b`, where `a >= b`.
+fn sub_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &x) in a.iter().enumerate() {
+        let mut diff = x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        } else {
+            borrow = 0;
+        }
+        result.push(diff as u32);
+    }
+    trim(&mut result);
+    result
+}
+
+fn mul_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = x as u64 * y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    trim(&mut result);
+    result
+}
+
+/// Divides by a single digit, returning the quotient and remainder.
+fn div_rem_digit(a: &[u32], b: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut rem = 0u64;
+    for i in (0..a.len()).rev() {
+        let cur = (rem << 32) | a[i] as u64;
+        quotient[i] = (cur / b as u64) as u32;
+        rem = cur % b as u64;
+    }
+    trim(&mut quotient);
+    (quotient, rem as u32)
+}
+
+fn bit_len(digits: &[u32]) -> usize {
+    match digits.last() {
+        Some(&top) => digits.len() * 32 - top.leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+fn shl_digits(a: &[u32], bits: usize) -> Vec<u32> {
+    if a.is_empty() {
+        return Vec::new();
+    }
+    let (words, bits) = (bits / 32, bits % 32);
+    let mut result = vec![0u32; words];
+    if bits == 0 {
+        result.extend_from_slice(a);
+    } else {
+        let mut carry = 0u32;
+        for &x in a {
+            result.push((x << bits) | carry);
+            carry = x >> (32 - bits);
+        }
+        if carry != 0 {
+            result.push(carry);
+        }
+    }
+    result
+}
+
+fn shr_digits(a: &[u32], bits: usize) -> Vec<u32> {
+    let (words, bits) = (bits / 32, bits % 32);
+    if words >= a.len() {
+        return Vec::new();
+    }
+    let a = &a[words..];
+    let mut result = Vec::with_capacity(a.len());
+    if bits == 0 {
+        result.extend_from_slice(a);
+    } else {
+        for i in 0..a.len() {
+            let high = a.get(i + 1).map_or(0, |&h| h << (32 - bits));
+            result.push((a[i] >> bits) | high);
+        }
+    }
+    trim(&mut result);
+    result
+}
+
+/// Divides magnitudes, returning the quotient and remainder.  `b` must be
+/// non-zero.
+fn div_rem_digits(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_digits(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let (q, r) = div_rem_digit(a, b[0]);
+        return (q, if r == 0 { Vec::new() } else { vec![r] });
+    }
+    // Binary long division.  Slow, but these numbers come from scripts.
+    let mut quotient = vec![0u32; a.len()];
+    let mut rem: Vec<u32> = Vec::new();
+    for i in (0..bit_len(a)).rev() {
+        rem = shl_digits(&rem, 1);
+        if (a[i / 32] >> (i % 32)) & 1 == 1 {
+            if rem.is_empty() {
+                rem.push(1);
+            } else {
+                rem[0] |= 1;
+            }
+        }
+        if cmp_digits(&rem, b) != Ordering::Less {
+            rem = sub_digits(&rem, b);
+            quotient[i / 32] |= 1 << (i % 32);
+        }
+    }
+    trim(&mut quotient);
+    (quotient, rem)
+}
+
+impl BigInt {
+    fn from_parts(negative: bool, mut digits: Vec<u32>) -> BigInt {
+        trim(&mut digits);
+        BigInt { negative: negative && !digits.is_empty(), digits }
+    }
+
+    pub fn zero() -> BigInt {
+        BigInt { negative: false, digits: Vec::new() }
+    }
+
+    pub fn one() -> BigInt {
+        BigInt::from(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt { negative: false, digits: self.digits.clone() }
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.digits.first().is_none_or(|d| d & 1 == 0)
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.digits.len() > 2 {
+            return None;
+        }
+        let magnitude = self.digits.iter().rev().fold(0u64, |acc, &d| (acc << 32) | d as u64);
+        if self.negative {
+            if magnitude <= i64::MAX as u64 + 1 {
+                Some((magnitude as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else if magnitude <= i64::MAX as u64 {
+            Some(magnitude as i64)
+        } else {
+            None
+        }
+    }
+
+    /// Converts to the nearest `f64`, or an infinity if out of range.
+    pub fn to_f64(&self) -> f64 {
+        // Parsing the decimal form gives correct rounding.
+        self.to_string().parse().unwrap()
+    }
+
+    /// Truncating division, returning the quotient and a remainder with the
+    /// sign of `self`.  Panics if `other` is zero.
+    pub fn div_rem(&self, other: &BigInt) -> (BigInt, BigInt) {
+        assert!(!other.is_zero(), "BigInt division by zero");
+        let (q, r) = div_rem_digits(&self.digits, &other.digits);
+        (BigInt::from_parts(self.negative != other.negative, q),
+         BigInt::from_parts(self.negative, r))
+    }
+
+    /// The greatest common divisor, which is never negative.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        // Binary GCD avoids the slow general division.
+        let mut a = self.digits.clone();
+        let mut b = other.digits.clone();
+        if a.is_empty() {
+            return BigInt::from_parts(false, b);
+        }
+        if b.is_empty() {
+            return BigInt::from_parts(false, a);
+        }
+        let trailing = |d: &[u32]| {
+            let i = d.iter().position(|&x| x != 0).unwrap();
+            i * 32 + d[i].trailing_zeros() as usize
+        };
+        let shift = ::std::cmp::min(trailing(&a), trailing(&b));
+        a = shr_digits(&a, trailing(&a));
+        loop {
+            b = shr_digits(&b, trailing(&b));
+            if cmp_digits(&a, &b) == Ordering::Greater {
+                ::std::mem::swap(&mut a, &mut b);
+            }
+            b = sub_digits(&b, &a);
+            if b.is_empty() {
+                return BigInt::from_parts(false, shl_digits(&a, shift));
+            }
+        }
+    }
+
+    pub fn bit_len(&self) -> usize {
+        bit_len(&self.digits)
+    }
+
+    /// Parses an optionally signed string of digits in `radix`, which must
+    /// be between 2 and 36.
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<BigInt, ParseBigIntError> {
+        let (negative, s) = match s.as_bytes().first() {
+            Some(&b'-') => (true, &s[1..]),
+            Some(&b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        if s.is_empty() || !s.chars().all(|c| c.is_digit(radix)) {
+            return Err(ParseBigIntError);
+        }
+        // Take as many digits at a time as fit in one base 2^32 digit.
+        let mut chunk_len = 1;
+        while (radix as u64).pow(chunk_len as u32 + 1) <= u32::MAX as u64 {
+            chunk_len += 1;
+        }
+        let mut digits = Vec::new();
+        for chunk in s.as_bytes().chunks(chunk_len) {
+            let chunk = ::std::str::from_utf8(chunk).unwrap();
+            let scale = radix.pow(chunk.len() as u32);
+            digits = mul_digits(&digits, &[scale]);
+            digits = add_digits(&digits, &[u32::from_str_radix(chunk, radix).unwrap()]);
+            trim(&mut digits);
+        }
+        Ok(BigInt::from_parts(negative, digits))
+    }
+
+    /// Formats in `radix`, which must be between 2 and 36, using lowercase
+    /// letters for digits above 9.
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        assert!((2..=36).contains(&radix), "radix must be between 2 and 36");
+        if self.is_zero() {
+            return String::from("0");
+        }
+        let mut chars = Vec::new();
+        let mut digits = self.digits.clone();
+        while !digits.is_empty() {
+            let (q, r) = div_rem_digit(&digits, radix);
+            chars.push(::std::char::from_digit(r, radix).unwrap());
+            digits = q;
+        }
+        if self.negative {
+            chars.push('-');
+        }
+        chars.iter().rev().collect()
+    }
+
+    pub fn shl(&self, bits: usize) -> BigInt {
+        BigInt::from_parts(self.negative, shl_digits(&self.digits, bits))
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(i: i64) -> Self {
+        let magnitude = i.unsigned_abs();
+        BigInt::from_parts(i < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_digits(&self.digits, &other.digits),
+            (true, true) => cmp_digits(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.digits.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_digits(&self.digits, &other.digits));
+        }
+        match cmp_digits(&self.digits, &other.digits) {
+            Ordering::Less => BigInt::from_parts(other.negative, sub_digits(&other.digits, &self.digits)),
+            _ => BigInt::from_parts(self.negative, sub_digits(&self.digits, &other.digits)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(self.negative != other.negative, mul_digits(&self.digits, &other.digits))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // Peel off nine decimal digits at a time.
+        let mut chunks = Vec::new();
+        let mut digits = self.digits.clone();
+        while !digits.is_empty() {
+            let (q, r) = div_rem_digit(&digits, 1_000_000_000);
+            chunks.push(r);
+            digits = q;
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseBigIntError;
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<BigInt, ParseBigIntError> {
+        BigInt::from_str_radix(s, 10)
+    }
+}
+
+/// An exact fraction kept in lowest terms with a positive denominator.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numer: BigInt,
+    denom: BigInt,
+}
+
+impl Rational {
+    /// Builds `numer / denom` in lowest terms.  Panics if `denom` is zero.
+    pub fn new(numer: BigInt, denom: BigInt) -> Rational {
+        assert!(!denom.is_zero(), "Rational with zero denominator");
+        if denom == BigInt::one() {
+            return Rational { numer, denom };
+        }
+        let gcd = numer.gcd(&denom);
+        let (mut numer, mut denom) = (numer.div_rem(&gcd).0, denom.div_rem(&gcd).0);
+        if denom.is_negative() {
+            numer = -&numer;
+            denom = -&denom;
+        }
+        Rational { numer, denom }
+    }
+
+    pub fn from_integer(i: BigInt) -> Rational {
+        Rational { numer: i, denom: BigInt::one() }
+    }
+
+    /// Gets the exact value of a finite `f64`, or `None` for an infinity or
+    /// NaN.
+    pub fn from_f64(f: f64) -> Option<Rational> {
+        if !f.is_finite() {
+            return None;
+        }
+        let bits = f.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = (bits & ((1 << 52) - 1)) as i64;
+        // Subnormals have no implicit leading bit.
+        let (mantissa, exponent) = if exponent == 0 {
+            (fraction, -1074)
+        } else {
+            (fraction | 1 << 52, exponent - 1075)
+        };
+        let mantissa = BigInt::from(if f < 0.0 { -mantissa } else { mantissa });
+        Some(if exponent >= 0 {
+            Rational::from_integer(mantissa.shl(exponent as usize))
+        } else {
+            Rational::new(mantissa, BigInt::one().shl(-exponent as usize))
+        })
+    }
+
+    pub fn numer(&self) -> &BigInt {
+        &self.numer
+    }
+
+    pub fn denom(&self) -> &BigInt {
+        &self.denom
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denom == BigInt::one()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        if self.numer.is_zero() {
+            return 0.0;
+        }
+        // Scale so the quotient has 64 significant bits, then undo the
+        // scaling in two steps so the intermediate power of two doesn't
+        // overflow or underflow.
+        let shift = 64 + self.denom.bit_len() as i64 - self.numer.bit_len() as i64;
+        let (n, d) = if shift >= 0 {
+            (self.numer.shl(shift as usize), self.denom.clone())
+        } else {
+            (self.numer.clone(), self.denom.shl(-shift as usize))
+        };
+        let q = n.div_rem(&d).0.to_f64();
+        let half = 2f64.powi(-(shift / 2) as i32);
+        q * half * 2f64.powi(-(shift - shift / 2) as i32)
+    }
+
+    /// Parses `n/d` in `radix`, where `d` is unsigned and non-zero.
+    pub fn from_str_radix(s: &str, radix: u32) -> Result<Rational, ParseBigIntError> {
+        let slash = s.find('/').ok_or(ParseBigIntError)?;
+        let (numer, denom) = (&s[..slash], &s[slash + 1..]);
+        if denom.starts_with('+') || denom.starts_with('-') {
+            return Err(ParseBigIntError);
+        }
+        let denom = BigInt::from_str_radix(denom, radix)?;
+        if denom.is_zero() {
+            return Err(ParseBigIntError);
+        }
+        Ok(Rational::new(BigInt::from_str_radix(numer, radix)?, denom))
+    }
+
+    pub fn recip(&self) -> Rational {
+        Rational::new(self.denom.clone(), self.numer.clone())
+    }
+
+    /// The largest integer no greater than `self`.
+    pub fn floor(&self) -> BigInt {
+        let (q, r) = self.numer.div_rem(&self.denom);
+        if r.is_negative() {
+            &q - &BigInt::one()
+        } else {
+            q
+        }
+    }
+
+    /// Formats as `n/d` in `radix`, which must be between 2 and 36.
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        format!("{}/{}", self.numer.to_str_radix(radix), self.denom.to_str_radix(radix))
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        (&self.numer * &other.denom).cmp(&(&other.numer * &self.denom))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &Rational {
+    type Output = Rational;
+
+    fn add(self, other: &Rational) -> Rational {
+        Rational::new(&(&self.numer * &other.denom) + &(&other.numer * &self.denom),
+                      &self.denom * &other.denom)
+    }
+}
+
+impl Sub for &Rational {
+    type Output = Rational;
+
+    fn sub(self, other: &Rational) -> Rational {
+        Rational::new(&(&self.numer * &other.denom) - &(&other.numer * &self.denom),
+                      &self.denom * &other.denom)
+    }
+}
+
+impl Mul for &Rational {
+    type Output = Rational;
+
+    fn mul(self, other: &Rational) -> Rational {
+        Rational::new(&self.numer * &other.numer, &self.denom * &other.denom)
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.numer, self.denom)
+    }
+}
+
+impl FromStr for Rational {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<Rational, ParseBigIntError> {
+        Rational::from_str_radix(s, 10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_bigint_parse_display() {
+        for s in &["0", "1", "-1", "4294967296", "-18446744073709551616",
+                   "123456789012345678901234567890123456789"] {
+            assert_eq!(*s, big(s).to_string());
+        }
+        assert_eq!("5", big("+0005").to_string());
+        assert_eq!("0", big("-0").to_string());
+        assert!("".parse::<BigInt>().is_err());
+        assert!("1a".parse::<BigInt>().is_err());
+        assert!("-".parse::<BigInt>().is_err());
+        assert_eq!(big("255"), BigInt::from_str_radix("fF", 16).unwrap());
+        assert_eq!(big("-10"), BigInt::from_str_radix("-1010", 2).unwrap());
+        assert_eq!(big("340282366920938463463374607431768211455"),
+                   BigInt::from_str_radix(&"f".repeat(32), 16).unwrap());
+        assert!(BigInt::from_str_radix("12", 2).is_err());
+        assert_eq!("ff", big("255").to_str_radix(16));
+        assert_eq!("-1010", big("-10").to_str_radix(2));
+        assert_eq!("0", BigInt::zero().to_str_radix(8));
+        assert_eq!("f".repeat(32), BigInt::from_str_radix(&"f".repeat(32), 16).unwrap().to_str_radix(16));
+    }
+
+    #[test]
+    fn test_bigint_i64() {
+        for &i in &[0, 1, -1, i64::MAX, i64::MIN, 1 << 40] {
+            assert_eq!(Some(i), BigInt::from(i).to_i64());
+            assert_eq!(i.to_string(), BigInt::from(i).to_string());
+        }
+        assert_eq!(None, big("9223372036854775808").to_i64());
+        assert_eq!(None, big("-9223372036854775809").to_i64());
+    }
+
+    #[test]
+    fn test_bigint_arithmetic() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+        assert_eq!(big("-864197532086419753208641975320"), &a + &b);
+        assert_eq!(big("1111111110111111111011111111100"), &a - &b);
+        assert_eq!(big("-121932631137021795226185032733622923332237463801111263526900"), &a * &b);
+        let (q, r) = b.div_rem(&a);
+        assert_eq!(big("-8"), q);
+        assert_eq!(big("-9000000000900000000090"), r);
+        assert_eq!(big("10"), big("-30").gcd(&big("70")));
+        assert_eq!(big("9000000000900000000090"), a.gcd(&b));
+    }
+
+    #[test]
+    fn test_rational_from_f64() {
+        let exact = |f| Rational::from_f64(f).unwrap().to_string();
+        assert_eq!("-3/4", exact(-0.75));
+        assert_eq!(Rational::from_integer(BigInt::one().shl(53)), Rational::from_f64(9007199254740992.0).unwrap());
+        assert_eq!(Rational::new(BigInt::one(), BigInt::one().shl(1074)), Rational::from_f64(5e-324).unwrap());
+        assert_eq!(Rational::from_integer(BigInt::zero()), Rational::from_f64(-0.0).unwrap());
+        assert_eq!(None, Rational::from_f64(f64::NAN));
+        assert_eq!(None, Rational::from_f64(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_bigint_to_f64() {
+        assert_eq!(1e30, big("1000000000000000000000000000000").to_f64());
+        assert_eq!(f64::INFINITY, big(&format!("1{}", "0".repeat(400))).to_f64());
+    }
+
+    #[test]
+    fn test_rational() {
+        assert_eq!("-3/2", Rational::new(big("6"), big("-4")).to_string());
+        assert_eq!("1/3", "2/6".parse::<Rational>().unwrap().to_string());
+        assert!("1/0".parse::<Rational>().is_err());
+        assert!("1/-2".parse::<Rational>().is_err());
+        let third: Rational = "1/3".parse().unwrap();
+        let sixth: Rational = "1/6".parse().unwrap();
+        assert_eq!("1/2", (&third + &sixth).to_string());
+        assert_eq!("1/6", (&third - &sixth).to_string());
+        assert_eq!("1/18", (&third * &sixth).to_string());
+        assert!(third.recip().is_integer());
+        assert_eq!(1.0 / 3.0, third.to_f64());
+        assert!(third > sixth);
+        assert_eq!(big("2"), "7/3".parse::<Rational>().unwrap().floor());
+        assert_eq!(big("-3"), "-7/3".parse::<Rational>().unwrap().floor());
+        assert_eq!("-1/11", "-1/3".parse::<Rational>().unwrap().to_str_radix(2));
+    }
+}
@@ -1,241 +1,818 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::rc::Rc;
 use types::*;
-use eval::eval;
+use eval::{eval, eval_value};
 use instance::Scib;
-use std::io::{Result, Error, ErrorKind};
+use bignum::BigInt;
+use lex::parse_number;
+use numeric::{Number, Rounding};
+use error::{Result, Error, ErrorKind};
 
-pub fn let_vars<'a, I: Iterator<Item=(String, Rc<Value>)>>(
-    scib: &mut Scib, args: I, to_eval: &Body) -> Result<Rc<Value>> {
-    let mut old_bound = Vec::new();
+pub fn let_vars<I: Iterator<Item=(String, Rc<Value>)>>(
+    scib: &mut Scib, parent: Option<Rc<Environment>>, args: I, body: &[Rc<Value>],
+    output: &mut Vec<Rc<Value>>) -> Result<()> {
+    let env = Environment::new(parent);
     for (name, value) in args {
-        if let Some(old_value) = scib.set(name.clone(), value.clone()) {
-            old_bound.push((name, old_value));
-        }
-    }
-    let result =
-        match to_eval {
-            &Body::Lisp(ref l) => {
-                let mut result = Rc::new(Value::Nil);
-                for v in l {
-                    result = try!(eval(scib, &v));
-                }
-                result
-            },
-            &Body::Rust(f) => try!(f(scib)),
-        };
-    for (name, value) in old_bound {
-        scib.set(name.clone(), value);
+        env.define(name, value);
     }
-    Ok(result)
+    let old_env = scib.set_environment(Some(Rc::new(env)));
+    let result = progn(scib, body, output);
+    scib.set_environment(old_env);
+    result
 }
 
-pub fn let_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let binds_list = scib.unbind("_let-binds").unwrap();
-    let body = Body::Lisp(
-        match Rc::try_unwrap(scib.unbind("_let-body").unwrap()) {
-            Ok(body) =>
-                match body {
-                    Value::List(l) => l,
-                    _ => unreachable!(),
-                },
-            Err(body) =>
-                match *body {
-                    Value::List(ref l) => l.clone(),
-                    _ => unreachable!(),
-                },
-        });
-    let binds_unparsed =
-        match *binds_list {
-            Value::List(ref l) => l,
-            _ => return Err(Error::new(ErrorKind::InvalidInput,
-                                       format!("let requires a list of bindings as its first parameter, found '{:?}'", *binds_list))),
-        };
+pub fn let_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let binds_list = &args[0];
+    let body = &args[1..];
+    let binds_unparsed = match binds_list.list_elements() {
+        Some(l) => l,
+        None => return Err(Error::new(ErrorKind::Syntax,
+                                      format!("let requires a list of bindings as its first parameter, found '{:?}'", *binds_list))),
+    };
     let mut binds: Vec<(String, Rc<Value>)> = Vec::with_capacity(binds_unparsed.len());
     for bind in binds_unparsed {
-        match **bind {
-            Value::List(ref l) => {
-                if l.len() == 2 {
-                    let n = match *l[0] {
-                        Value::Label(ref l) => l.clone(),
-                        _ => return Err(Error::new(ErrorKind::InvalidInput,
-                                                   format!("let requires a binding to have a label as it's name"))),
-                    };
-                    binds.push((n, l[1].clone()));
-                } else {
-                    return Err(Error::new(ErrorKind::InvalidInput,
-                                          format!("let requires a binding to have a name and a value only")));
-                }
-            },
-            Value::Label(ref l) => {
-                binds.push((l.clone(), Rc::new(Value::Nil)));
+        if let Value::Label(ref l) = *bind {
+            binds.push((l.clone(), Rc::new(Value::Nil)));
+            continue;
+        }
+        match bind.list_elements() {
+            Some(ref l) if l.len() == 2 => {
+                let n = match *l[0] {
+                    Value::Label(ref l) => l.clone(),
+                    _ => return Err(Error::new(ErrorKind::Syntax,
+                                               "let requires a binding to have a label as it's name")),
+                };
+                binds.push((n, eval_value(scib, &l[1])?));
             },
-            _ => return Err(Error::new(ErrorKind::InvalidInput,
-                                       format!("let requires each binding to fit the form '(name value)' or 'name', found {:?}", *bind))),
+            Some(_) => return Err(Error::new(ErrorKind::Syntax,
+                                             "let requires a binding to have a name and a value only")),
+            None => return Err(Error::new(ErrorKind::Syntax,
+                                          format!("let requires each binding to fit the form '(name value)' or 'name', found {:?}", *bind))),
         }
     }
 
-    let_vars(scib, binds.iter().cloned(), &body)
+    let mut output = Vec::with_capacity(1);
+    let env = scib.environment();
+    let_vars(scib, env, binds.into_iter(), body, &mut output)?;
+    Ok(Rc::new(Value::Quote(output.pop().unwrap_or_else(|| Rc::new(Value::Nil)))))
 }
 
-pub fn setq_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let label = match *scib.unbind("_setq-label").unwrap() {
+pub fn error_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let message = match *args[0] {
+        Value::String(ref s) => s.clone(),
+        ref v => v.to_string(),
+    };
+    Err(Error::new(ErrorKind::User(args[0].clone()), message))
+}
+
+pub fn setq_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let label = match *args[0] {
         Value::Label(ref l) => l.clone(),
-        ref label => return Err(Error::new(ErrorKind::InvalidInput,
+        ref label => return Err(Error::new(ErrorKind::Syntax,
                                            format!("setq's first argument must be a label, found '{:?}'", label))),
     };
-    let value = scib.unbind("_setq-value").unwrap();
-    let value = try!(eval(scib, &value));
-    scib.set(label, value.clone());
+    let value = eval_value(scib, &args[1])?;
+    scib.assign(label, value.clone());
     Ok(Rc::new(Value::Quote(value)))
 }
 
-pub fn equalsign_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let rest = match *scib.unbind("_=-rest").unwrap() {
-        Value::List(ref l) => l.clone(),
-        _ => panic!(),
+fn boolean(b: bool) -> Rc<Value> {
+    Rc::new(if b { Value::True } else { Value::Nil })
+}
+
+/// Checks that `holds` is true of each adjacent pair of the numbers in
+/// `args`.  Every argument must be a number, even after a pair fails.
+fn compare_chain(function: &str, args: &[Rc<Value>], holds: fn(Ordering) -> bool) -> Result<Rc<Value>> {
+    let numbers = args.iter().map(|v| Number::from_value(function, v)).collect::<Result<Vec<_>>>()?;
+    Ok(boolean(numbers.windows(2).all(|w| w[0].compare(&w[1]).is_some_and(holds))))
+}
+
+pub fn equalsign_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    compare_chain("=", args, |o| o == Ordering::Equal)
+}
+
+pub fn less_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    compare_chain("<", args, |o| o == Ordering::Less)
+}
+
+pub fn greater_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    compare_chain(">", args, |o| o == Ordering::Greater)
+}
+
+pub fn less_equal_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    compare_chain("<=", args, |o| o != Ordering::Greater)
+}
+
+pub fn greater_equal_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    compare_chain(">=", args, |o| o != Ordering::Less)
+}
+
+/// Checks that no two of the numbers given are equal.
+pub fn not_equal_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let numbers = args.iter().map(|v| Number::from_value("/=", v)).collect::<Result<Vec<_>>>()?;
+    Ok(boolean(numbers.iter().enumerate().all(|(i, a)| {
+        numbers[i + 1..].iter().all(|b| a.compare(b) != Some(Ordering::Equal))
+    })))
+}
+
+pub fn not_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(boolean(!is_true(&args[0])))
+}
+
+/// Evaluates each argument until one is `nil`.  The last argument is
+/// left for the caller to evaluate, like the branches of `if`.
+pub fn and_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let (last, init) = match args.split_last() {
+        Some(split) => split,
+        None => return Ok(Rc::new(Value::True)),
     };
-    let first = scib.unbind("_=-first").unwrap();
-    if rest.iter().all(|v| first == *v) {
-        Ok(Rc::new(Value::True))
-    } else {
-        Ok(Rc::new(Value::Nil))
+    for arg in init {
+        if !is_true(&*eval_value(scib, arg)?) {
+            return Ok(Rc::new(Value::Nil));
+        }
     }
+    Ok(last.clone())
 }
 
-pub fn sum_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let values = match *scib.unbind("_+").unwrap() {
-        Value::List(ref l) => l.clone(),
-        ref label => panic!(),
+/// Evaluates each argument until one is not `nil`, returning it.
+pub fn or_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let (last, init) = match args.split_last() {
+        Some(split) => split,
+        None => return Ok(Rc::new(Value::Nil)),
     };
-    let mut res = 0.0;
-    for value in values {
-        res += match *value {
-            Value::Number(n) => n,
-            ref label => return Err(Error::new(ErrorKind::InvalidInput,
-                                               format!("+'s arguments must all be numbers, found '{:?}'", label))),
-        };
+    for arg in init {
+        let value = eval_value(scib, arg)?;
+        if is_true(&value) {
+            return Ok(Rc::new(Value::Quote(value)));
+        }
+    }
+    Ok(last.clone())
+}
+
+/// Whether `a` and `b` are the same object.  `nil`, `t`, labels with the
+/// same name, and equal integers and characters are always the same.
+pub fn is_eq(a: &Rc<Value>, b: &Rc<Value>) -> bool {
+    if Rc::ptr_eq(a, b) {
+        return true;
+    }
+    match (&**a, &**b) {
+        (Value::Nil, Value::Nil) | (Value::True, Value::True) => true,
+        (Value::Label(x), Value::Label(y)) => x == y,
+        (Value::Integer(x), Value::Integer(y)) => x == y,
+        (Value::Char(x), Value::Char(y)) => x == y,
+        (Value::Function(x), Value::Function(y)) | (Value::Macro(x), Value::Macro(y)) => Rc::ptr_eq(x, y),
+        _ => false,
+    }
+}
+
+/// Like `is_eq`, but also equal for numbers of the same exactness and
+/// value.  Floats are compared bit for bit, so `0.0` and `-0.0` differ.
+pub fn is_eqv(a: &Rc<Value>, b: &Rc<Value>) -> bool {
+    match (&**a, &**b) {
+        (Value::BigInt(x), Value::BigInt(y)) => x == y,
+        (Value::Rational(x), Value::Rational(y)) => x == y,
+        (Value::Number(x), Value::Number(y)) => x.to_bits() == y.to_bits(),
+        _ => is_eq(a, b),
     }
-    Ok(Rc::new(Value::Number(res)))
 }
 
-pub fn difference_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let mut res = match *scib.unbind("_--positive").unwrap() {
-        Value::Number(n) => n,
-        ref label => return Err(Error::new(ErrorKind::InvalidInput,
-                                           format!("-'s arguments must all be numbers, found '{:?}'", label))),
+/// Whether `a` and `b` have the same structure, comparing their atoms with
+/// `is_eqv` and strings by their contents.
+pub fn is_equal(a: &Rc<Value>, b: &Rc<Value>) -> bool {
+    equal_in(a, b, &mut Vec::new())
+}
+
+/// Implements `is_equal`.  `comparing` holds the pairs of pairs being
+/// compared around `a` and `b`.  Meeting one again means both sides loop
+/// back the same way, so they are taken as equal and circular lists end.
+fn equal_in(a: &Rc<Value>, b: &Rc<Value>, comparing: &mut Vec<(*const Value, *const Value)>) -> bool {
+    let depth = comparing.len();
+    let (mut a, mut b) = (a.clone(), b.clone());
+    // Walk the cdrs in a loop so long lists don't use up the stack.
+    let equal = loop {
+        if is_eqv(&a, &b) {
+            break true;
+        }
+        let (x, y) = match (&*a, &*b) {
+            (Value::Pair(x), Value::Pair(y)) => (x, y),
+            (Value::String(x), Value::String(y)) => break x == y,
+            (Value::Quote(x), Value::Quote(y)) |
+            (Value::Backquote(x), Value::Backquote(y)) |
+            (Value::Unquote(x), Value::Unquote(y)) |
+            (Value::UnquoteList(x), Value::UnquoteList(y)) => break equal_in(x, y, comparing),
+            _ => break false,
+        };
+        let key = (Rc::as_ptr(&a), Rc::as_ptr(&b));
+        if comparing.contains(&key) {
+            break true;
+        }
+        comparing.push(key);
+        if !equal_in(&x.car(), &y.car(), comparing) {
+            break false;
+        }
+        let (x_cdr, y_cdr) = (x.cdr(), y.cdr());
+        a = x_cdr;
+        b = y_cdr;
     };
-    let mut values = match *scib.unbind("_--negatives").unwrap() {
-        Value::List(ref l) => l.clone(),
-        ref label => panic!(),
+    comparing.truncate(depth);
+    equal
+}
+
+pub fn eq_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(boolean(is_eq(&args[0], &args[1])))
+}
+
+pub fn eqv_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(boolean(is_eqv(&args[0], &args[1])))
+}
+
+pub fn equal_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(boolean(is_equal(&args[0], &args[1])))
+}
+
+pub fn sum_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut res = Number::Integer(0);
+    for value in args {
+        res = res.add(Number::from_value("+", value)?);
+    }
+    Ok(res.into_value())
+}
+
+pub fn difference_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut res = Number::from_value("-", &args[0])?;
+    for value in &args[1..] {
+        res = res.sub(Number::from_value("-", value)?);
+    }
+    Ok(res.into_value())
+}
+
+pub fn product_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut res = Number::Integer(1);
+    for value in args {
+        res = res.mul(Number::from_value("*", value)?);
+    }
+    Ok(res.into_value())
+}
+
+pub fn divide_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut res = Number::from_value("/", &args[0])?;
+    for value in &args[1..] {
+        res = res.div(Number::from_value("/", value)?)?;
+    }
+    Ok(res.into_value())
+}
+
+pub fn quotient_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let a = Number::from_value("quotient", &args[0])?;
+    Ok(a.quotient(Number::from_value("quotient", &args[1])?)?.into_value())
+}
+
+pub fn rem_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let a = Number::from_value("rem", &args[0])?;
+    Ok(a.rem(Number::from_value("rem", &args[1])?)?.into_value())
+}
+
+pub fn mod_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let a = Number::from_value("mod", &args[0])?;
+    Ok(a.modulo(Number::from_value("mod", &args[1])?)?.into_value())
+}
+
+pub fn abs_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Number::from_value("abs", &args[0])?.abs().into_value())
+}
+
+/// Picks the number for which `wanted` is the result of comparing it with
+/// each of the others.  If any number is a float the result is too.
+fn extremum(function: &str, args: &[Rc<Value>], wanted: Ordering) -> Result<Rc<Value>> {
+    let numbers = args.iter().map(|v| Number::from_value(function, v)).collect::<Result<Vec<_>>>()?;
+    if numbers.iter().any(|n| n.to_f64().is_nan()) {
+        return Ok(Number::Float(f64::NAN).into_value());
+    }
+    let inexact = numbers.iter().any(|n| !n.is_exact());
+    let mut numbers = numbers.into_iter();
+    let mut best = numbers.next().unwrap();
+    for n in numbers {
+        if n.compare(&best) == Some(wanted) {
+            best = n;
+        }
+    }
+    if inexact {
+        best = Number::Float(best.to_f64());
+    }
+    Ok(best.into_value())
+}
+
+pub fn min_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    extremum("min", args, Ordering::Less)
+}
+
+pub fn max_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    extremum("max", args, Ordering::Greater)
+}
+
+pub fn floor_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Number::from_value("floor", &args[0])?.round(Rounding::Floor).into_value())
+}
+
+pub fn ceiling_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Number::from_value("ceiling", &args[0])?.round(Rounding::Ceiling).into_value())
+}
+
+pub fn round_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Number::from_value("round", &args[0])?.round(Rounding::Round).into_value())
+}
+
+pub fn truncate_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Number::from_value("truncate", &args[0])?.round(Rounding::Truncate).into_value())
+}
+
+pub fn expt_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let base = Number::from_value("expt", &args[0])?;
+    Ok(base.expt(Number::from_value("expt", &args[1])?)?.into_value())
+}
+
+pub fn sqrt_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Number::from_value("sqrt", &args[0])?.sqrt().into_value())
+}
+
+/// Applies `f` to the argument of `function` as a float.
+fn float_fn(function: &str, v: &Value, f: fn(f64) -> f64) -> Result<Rc<Value>> {
+    Ok(Rc::new(Value::Number(f(Number::from_value(function, v)?.to_f64()))))
+}
+
+pub fn exp_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    float_fn("exp", &args[0], f64::exp)
+}
+
+/// The natural logarithm, or the logarithm in `base` if one is given.
+pub fn log_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let x = Number::from_value("log", &args[0])?.to_f64();
+    Ok(Rc::new(Value::Number(match args.get(1) {
+        Some(base) => x.ln() / Number::from_value("log", base)?.to_f64().ln(),
+        None => x.ln(),
+    })))
+}
+
+pub fn sin_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    float_fn("sin", &args[0], f64::sin)
+}
+
+pub fn cos_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    float_fn("cos", &args[0], f64::cos)
+}
+
+pub fn tan_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    float_fn("tan", &args[0], f64::tan)
+}
+
+pub fn asin_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    float_fn("asin", &args[0], f64::asin)
+}
+
+pub fn acos_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    float_fn("acos", &args[0], f64::acos)
+}
+
+/// The arctangent of `y`, or of `y / x` using the signs of both to pick
+/// the quadrant if `x` is given.
+pub fn atan_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let y = Number::from_value("atan", &args[0])?.to_f64();
+    Ok(Rc::new(Value::Number(match args.get(1) {
+        Some(x) => y.atan2(Number::from_value("atan", x)?.to_f64()),
+        None => y.atan(),
+    })))
+}
+
+/// Gets an optional radix argument, which defaults to 10.
+fn radix_arg(function: &str, v: Option<&Rc<Value>>, supported: fn(i64) -> bool) -> Result<u32> {
+    let v = match v {
+        Some(v) => v,
+        None => return Ok(10),
     };
-    for value in values {
-        res -= match *value {
-            Value::Number(n) => n,
-            ref label => return Err(Error::new(ErrorKind::InvalidInput,
-                                               format!("-'s arguments must all be numbers, found '{:?}'", label))),
-        };
+    match **v {
+        Value::Integer(r) if supported(r) => Ok(r as u32),
+        Value::Integer(r) => Err(Error::new(ErrorKind::Range,
+                                            format!("{}: unsupported radix {}", function, r))),
+        ref v => Err(Error::new(ErrorKind::Type,
+                                format!("{} requires an integer radix, found '{:?}'", function, v))),
     }
-    Ok(Rc::new(Value::Number(res)))
 }
 
-pub fn product_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let values = match *scib.unbind("_*").unwrap() {
-        Value::List(ref l) => l.clone(),
-        ref label => panic!(),
+/// Formats a number as the reader would read it, in `radix` if given.
+/// Only exact numbers can be formatted in a radix other than 10.
+pub fn number_to_string_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let radix = radix_arg("number->string", args.get(1), |r| (2..=36).contains(&r))?;
+    let s = match Number::from_value("number->string", &args[0])? {
+        _ if radix == 10 => args[0].to_string(),
+        Number::Integer(i) => BigInt::from(i).to_str_radix(radix),
+        Number::Big(i) => i.to_str_radix(radix),
+        Number::Rational(r) => r.to_str_radix(radix),
+        Number::Float(_) => return Err(Error::new(ErrorKind::Range,
+                                                  "number->string: floats can only be formatted in radix 10")),
     };
-    let mut res = 1.0;
-    for value in values {
-        res *= match *value {
-            Value::Number(n) => n,
-            ref label => return Err(Error::new(ErrorKind::InvalidInput,
-                                               format!("+'s arguments must all be numbers, found '{:?}'", label))),
-        };
+    Ok(Rc::new(Value::String(s)))
+}
+
+/// Reads a number written as the reader accepts it, in `radix` if given,
+/// or returns `nil` if the string isn't a number.
+pub fn string_to_number_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let s = match *args[0] {
+        Value::String(ref s) => s,
+        ref v => return Err(Error::new(ErrorKind::Type,
+                                       format!("string->number requires a string, found '{:?}'", v))),
+    };
+    let prefix = match radix_arg("string->number", args.get(1), |r| [2, 8, 10, 16].contains(&r))? {
+        2 => "#b",
+        8 => "#o",
+        16 => "#x",
+        _ => "",
+    };
+    Ok(Rc::new(parse_number(&format!("{}{}", prefix, s)).unwrap_or(Value::Nil)))
+}
+
+pub fn zero_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let n = Number::from_value("zero?", &args[0])?;
+    Ok(boolean(n.compare(&Number::Integer(0)) == Some(Ordering::Equal)))
+}
+
+pub fn even_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let n = Number::from_value("even?", &args[0])?;
+    Ok(boolean(n.rem(Number::Integer(2))?.compare(&Number::Integer(0)) == Some(Ordering::Equal)))
+}
+
+pub fn odd_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let n = Number::from_value("odd?", &args[0])?;
+    Ok(boolean(n.rem(Number::Integer(2))?.compare(&Number::Integer(0)) != Some(Ordering::Equal)))
+}
+
+pub fn char_to_integer_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Rc::new(Value::Integer(args[0].as_char()? as i64)))
+}
+
+pub fn integer_to_char_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let i = match *args[0] {
+        Value::Integer(i) => i,
+        ref v => return Err(Error::new(ErrorKind::Type,
+                                       format!("integer->char requires an integer, found '{:?}'", v))),
+    };
+    match u32::try_from(i).ok().and_then(::std::char::from_u32) {
+        Some(c) => Ok(Rc::new(Value::Char(c))),
+        None => Err(Error::new(ErrorKind::Range,
+                               format!("integer->char: {} is not a Unicode scalar value", i))),
     }
-    Ok(Rc::new(Value::Number(res)))
 }
 
-pub fn quotient_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let mut res = match *scib.unbind("_/-numerator").unwrap() {
-        Value::Number(n) => n,
-        ref label => return Err(Error::new(ErrorKind::InvalidInput,
-                                           format!("/'s arguments must all be numbers, found '{:?}'", label))),
+pub fn string_ref_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let s = match *args[0] {
+        Value::String(ref s) => s,
+        ref v => return Err(Error::new(ErrorKind::Type,
+                                       format!("string-ref requires a string, found '{:?}'", v))),
     };
-    let values = match *scib.unbind("_/-denominator").unwrap() {
-        Value::List(ref l) => l.clone(),
-        ref label => panic!(),
+    let i = match *args[1] {
+        Value::Integer(i) => i,
+        ref v => return Err(Error::new(ErrorKind::Type,
+                                       format!("string-ref requires an integer index, found '{:?}'", v))),
     };
-    for value in values {
-        res /= match *value {
-            Value::Number(n) => n,
-            ref label => return Err(Error::new(ErrorKind::InvalidInput,
-                                               format!("/'s arguments must all be numbers, found '{:?}'", label))),
+    match usize::try_from(i).ok().and_then(|i| s.chars().nth(i)) {
+        Some(c) => Ok(Rc::new(Value::Char(c))),
+        None => Err(Error::new(ErrorKind::Range,
+                               format!("string-ref: index {} out of range for a string of length {}", i, s.chars().count()))),
+    }
+}
+
+pub fn list_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Value::list(args.iter().cloned()))
+}
+
+pub fn cons_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Value::cons(args[0].clone(), args[1].clone()))
+}
+
+pub fn car_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    match *args[0] {
+        Value::Pair(ref p) => Ok(p.car()),
+        Value::Nil => Ok(Rc::new(Value::Nil)),
+        ref v => Err(not_a_list("car", v)),
+    }
+}
+
+/// Gets the rest of a list, which is shared with it rather than copied.
+pub fn cdr_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    match *args[0] {
+        Value::Pair(ref p) => Ok(p.cdr()),
+        Value::Nil => Ok(Rc::new(Value::Nil)),
+        ref v => Err(not_a_list("cdr", v)),
+    }
+}
+
+fn not_a_list(function: &str, list: &Value) -> Error {
+    Error::new(ErrorKind::Type, format!("{} requires a list, found '{:?}'", function, list))
+}
+
+/// Gets the elements of a proper list argument to `function`.
+fn list_arg(function: &str, v: &Value) -> Result<Vec<Rc<Value>>> {
+    v.list_elements().ok_or_else(|| not_a_list(function, v))
+}
+
+fn index_arg(function: &str, v: &Value) -> Result<usize> {
+    match *v {
+        Value::Integer(i) => usize::try_from(i).map_err(|_| Error::new(ErrorKind::Range,
+                                                                      format!("{}: index {} is negative", function, i))),
+        ref v => Err(Error::new(ErrorKind::Type,
+                                format!("{} requires an integer index, found '{:?}'", function, v))),
+    }
+}
+
+pub fn length_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Rc::new(Value::Integer(list_arg("length", &args[0])?.len() as i64)))
+}
+
+/// Gets element `n` of a list, or `nil` if the list is too short.
+pub fn nth_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let n = index_arg("nth", &args[0])?;
+    Ok(list_arg("nth", &args[1])?.get(n).cloned().unwrap_or_else(|| Rc::new(Value::Nil)))
+}
+
+/// Like `nth`, but takes the list first and fails if the list is too
+/// short.
+pub fn list_ref_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let list = list_arg("list-ref", &args[0])?;
+    let i = index_arg("list-ref", &args[1])?;
+    list.get(i).cloned().ok_or_else(|| Error::new(ErrorKind::Range,
+                                                  format!("list-ref: index {} out of range for a list of length {}", i, list.len())))
+}
+
+/// Joins lists together.  The last argument is shared rather than copied,
+/// and need not be a list.
+pub fn append_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let (last, init) = match args.split_last() {
+        Some(split) => split,
+        None => return Ok(Rc::new(Value::Nil)),
+    };
+    let mut elements = Vec::new();
+    for list in init {
+        elements.extend(list_arg("append", list)?);
+    }
+    Ok(elements.into_iter().rev().fold(last.clone(), |cdr, car| Value::cons(car, cdr)))
+}
+
+pub fn reverse_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut list = list_arg("reverse", &args[0])?;
+    list.reverse();
+    Ok(Value::list(list))
+}
+
+/// Gets the last pair of a list, which holds the last element.  The pair
+/// is shared, so `set-cdr!` on it extends the list.
+pub fn last_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut tail = args[0].clone();
+    loop {
+        let next = match *tail {
+            Value::Pair(ref p) => match *p.cdr() {
+                Value::Pair(_) => p.cdr(),
+                _ => return Ok(tail.clone()),
+            },
+            Value::Nil => return Ok(tail.clone()),
+            _ => return Err(not_a_list("last", &args[0])),
+        };
+        tail = next;
+    }
+}
+
+/// Gets the tail of a list starting at the first element `equal` to `item`,
+/// or `nil` if there is none.
+pub fn member_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut tail = args[1].clone();
+    loop {
+        let next = match *tail {
+            Value::Pair(ref p) if is_equal(&p.car(), &args[0]) => return Ok(tail.clone()),
+            Value::Pair(ref p) => p.cdr(),
+            Value::Nil => return Ok(tail.clone()),
+            _ => return Err(not_a_list("member", &args[1])),
+        };
+        tail = next;
+    }
+}
+
+/// Gets the first entry of an association list whose key is `equal` to
+/// `key`.  Entries may be dotted pairs such as `(key . value)` or lists
+/// such as `(key value)`.
+pub fn assoc_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    for entry in list_arg("assoc", &args[1])? {
+        let found = match *entry {
+            Value::Pair(ref p) => is_equal(&p.car(), &args[0]),
+            Value::Nil => false,
+            _ => return Err(Error::new(ErrorKind::Type,
+                                       format!("assoc requires a list of pairs, found entry '{:?}'", entry))),
         };
+        if found {
+            return Ok(entry);
+        }
+    }
+    Ok(Rc::new(Value::Nil))
+}
+
+pub fn null_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Rc::new(match *args[0] {
+        Value::Nil => Value::True,
+        _ => Value::Nil,
+    }))
+}
+
+fn is_true(v: &Value) -> bool {
+    *v != Value::Nil
+}
+
+/// Lines up the elements of `lists`, giving the arguments for each call
+/// of a function mapped over them.  Stops at the end of the shortest list.
+fn zip_lists(function: &str, lists: &[Rc<Value>]) -> Result<Vec<Vec<Rc<Value>>>> {
+    let lists = lists.iter().map(|l| list_arg(function, l)).collect::<Result<Vec<_>>>()?;
+    let len = lists.iter().map(Vec::len).min().unwrap_or(0);
+    Ok((0..len).map(|i| lists.iter().map(|l| l[i].clone()).collect()).collect())
+}
+
+pub fn funcall_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    scib.call(&args[0], &args[1..])
+}
+
+/// Calls a function with the given arguments followed by the elements of
+/// the last argument, which must be a list.
+pub fn apply_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut call_args = Vec::new();
+    if let Some((list, init)) = args[1..].split_last() {
+        call_args.extend(init.iter().cloned());
+        call_args.extend(list_arg("apply", list)?);
+    }
+    scib.call(&args[0], &call_args)
+}
+
+pub fn map_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut result = Vec::new();
+    for call_args in zip_lists("map", &args[1..])? {
+        result.push(scib.call(&args[0], &call_args)?);
     }
-    Ok(Rc::new(Value::Number(res)))
+    Ok(Value::list(result))
 }
 
-pub fn list_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    Ok(scib.unbind("_list-rest").unwrap())
+pub fn filter_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut result = Vec::new();
+    for v in list_arg("filter", &args[1])? {
+        if is_true(&*scib.call(&args[0], ::std::slice::from_ref(&v))?) {
+            result.push(v);
+        }
+    }
+    Ok(Value::list(result))
 }
 
-pub fn progn_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let rest = match *scib.unbind("_progn-rest").unwrap() {
-        Value::List(ref l) => l.clone(),
-        _ => panic!(),
+/// Combines the elements of a list from the left with a function of two
+/// arguments, starting from `initial` if given.  Without `initial`, an
+/// empty list gives the result of calling the function with no arguments.
+pub fn reduce_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut elements = list_arg("reduce", &args[1])?.into_iter();
+    let mut acc = match args.get(2).cloned().or_else(|| elements.next()) {
+        Some(acc) => acc,
+        None => return scib.call(&args[0], &[]),
     };
-    Ok(Rc::new(Value::Quote(try!(progn(scib, &rest)))))
+    for v in elements {
+        acc = scib.call(&args[0], &[acc, v])?;
+    }
+    Ok(acc)
 }
 
-pub fn progn(scib: &mut Scib, exprs: &Vec<Rc<Value>>) -> Result<Rc<Value>> {
-    let mut res = Rc::new(Value::Nil);
+/// A stable merge sort, since the comparison can fail and needn't be a
+/// consistent order.
+fn merge_sort(scib: &mut Scib, less: &Rc<Value>, mut v: Vec<Rc<Value>>) -> Result<Vec<Rc<Value>>> {
+    if v.len() <= 1 {
+        return Ok(v);
+    }
+    let right = v.split_off(v.len() / 2);
+    let mut left = merge_sort(scib, less, v)?.into_iter().peekable();
+    let mut right = merge_sort(scib, less, right)?.into_iter().peekable();
+    let mut result = Vec::with_capacity(left.len() + right.len());
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if is_true(&*scib.call(less, &[r.clone(), l.clone()])?) {
+            result.extend(right.next());
+        } else {
+            result.extend(left.next());
+        }
+    }
+    result.extend(left);
+    result.extend(right);
+    Ok(result)
+}
+
+/// Sorts a list into a new list, ordered by a function that returns
+/// non-nil when its first argument should come before its second.
+pub fn sort_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let list = list_arg("sort", &args[0])?;
+    Ok(Value::list(merge_sort(scib, &args[1], list)?))
+}
+
+/// Returns the last result of the predicate if it holds for every element,
+/// or `t` for empty lists.
+pub fn every_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut result = Rc::new(Value::True);
+    for call_args in zip_lists("every", &args[1..])? {
+        result = scib.call(&args[0], &call_args)?;
+        if !is_true(&result) {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the first non-nil result of the predicate, or `nil`.
+pub fn some_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    for call_args in zip_lists("some", &args[1..])? {
+        let result = scib.call(&args[0], &call_args)?;
+        if is_true(&result) {
+            return Ok(result);
+        }
+    }
+    Ok(Rc::new(Value::Nil))
+}
+
+/// Gets the pair to change for `set-car!` or `set-cdr!`.
+fn as_pair<'a>(function: &str, v: &'a Value) -> Result<&'a Pair> {
+    match *v {
+        Value::Pair(ref p) => Ok(p),
+        _ => Err(Error::new(ErrorKind::Type,
+                            format!("{} requires a pair, found '{:?}'", function, v))),
+    }
+}
+
+pub fn set_car_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    *as_pair("set-car!", &args[0])?.car.borrow_mut() = args[1].clone();
+    Ok(args[1].clone())
+}
+
+pub fn set_cdr_f(_: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    *as_pair("set-cdr!", &args[0])?.cdr.borrow_mut() = args[1].clone();
+    Ok(args[1].clone())
+}
+
+pub fn progn_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    Ok(Rc::new(Value::Quote(progn_value(scib, args)?)))
+}
+
+/// Evaluates each of `exprs` in order, splicing the values produced by
+/// the last one into `output`.
+pub fn progn(scib: &mut Scib, exprs: &[Rc<Value>], output: &mut Vec<Rc<Value>>) -> Result<()> {
+    let mut res = Vec::with_capacity(1);
     for expr in exprs {
-        res = try!(eval(scib, expr));
+        res.clear();
+        eval(scib, expr, &mut res)?;
     }
-    Ok(res)
+    output.extend(res);
+    Ok(())
 }
 
-pub fn if_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let cond = match *scib.unbind("_if-cond").unwrap() {
-        Value::Nil => false,
-        _ => true,
-    };
-    let iftrue = scib.unbind("_if-iftrue").unwrap();
-    let iffalse = match *scib.unbind("_if-iffalse").unwrap() {
-        Value::List(ref l) => l.clone(),
-        _ => panic!(),
-    };
+/// Evaluates each of `exprs` in order, returning the last value produced
+/// or `nil` if there were none.
+pub fn progn_value(scib: &mut Scib, exprs: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let mut res = Vec::with_capacity(1);
+    progn(scib, exprs, &mut res)?;
+    Ok(res.pop().unwrap_or_else(|| Rc::new(Value::Nil)))
+}
+
+pub fn if_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let cond = *eval_value(scib, &args[0])? != Value::Nil;
     if cond {
-        Ok(iftrue)
+        Ok(args[1].clone())
     } else {
-        Ok(Rc::new(Value::Quote(try!(progn(scib, &iffalse)))))
+        Ok(Rc::new(Value::Quote(progn_value(scib, &args[2..])?)))
     }
 }
 
-fn define_parse_params(l: &Vec<Rc<Value>>) -> Result<(String, Parameters)> {
-    let mut required = Vec::new();
-    let mut optional = Vec::new();
-    let mut rest = None;
+fn define_parse_params(l: &[Rc<Value>]) -> Result<(String, Parameters)> {
     let mut iter = l.iter();
     let name =
-        try!(
-            try!(iter.next().ok_or(
-                Error::new(ErrorKind::InvalidInput,
-                           format!("A name is required."))))
-                .as_label())
+        iter.next().ok_or(
+            Error::new(ErrorKind::Syntax,
+                       "A name is required."))?
+        .as_label()?
         .clone();
+    Ok((name, parse_params(iter)?))
+}
+
+fn parse_params<'a, I: Iterator<Item = &'a Rc<Value>>>(mut iter: I) -> Result<Parameters> {
+    let mut required = Vec::new();
+    let mut optional = Vec::new();
+    let mut rest = None;
     while let Some(param) = iter.next() {
-        let param = try!(param.as_label());
+        let param = param.as_label()?;
         if param == "&optional" {
             while let Some(param) = iter.next() {
-                let param = try!(param.as_label());
+                let param = param.as_label()?;
                 if param == "&rest" {
-                    rest = Some(try!(try!(iter.next().ok_or(
-                        Error::new(ErrorKind::InvalidInput,
-                                   format!("&rest must be named."))))
-                                     .as_label()).clone());
+                    rest = Some(iter.next().ok_or(
+                        Error::new(ErrorKind::Syntax,
+                                   "&rest must be named."))?
+                                     .as_label()?.clone());
                     if iter.next().is_some() {
-                        return Err(Error::new(ErrorKind::InvalidInput,
-                                              format!("&rest cannot be named multiple times.")));
+                        return Err(Error::new(ErrorKind::Syntax,
+                                              "&rest cannot be named multiple times."));
                     }
                     break;
                 } else {
@@ -243,65 +820,84 @@ fn define_parse_params(l: &Vec<Rc<Value>>) -> Result<(String, Parameters)> {
                 }
             }
             if optional.is_empty() {
-                return Err(Error::new(ErrorKind::InvalidInput,
-                                      format!("No optional arguments given.")));
+                return Err(Error::new(ErrorKind::Syntax,
+                                      "No optional arguments given."));
             }
             break;
         } else if param == "&rest" {
-            rest = Some(try!(try!(iter.next().ok_or(Error::new(ErrorKind::InvalidInput,
-                                                               format!("&rest must be named."))))
-                             .as_label()).clone());
+            rest = Some(iter.next().ok_or(Error::new(ErrorKind::Syntax,
+                                                               "&rest must be named."))?
+                             .as_label()?.clone());
             if iter.next().is_some() {
-                return Err(Error::new(ErrorKind::InvalidInput,
-                                      format!("&rest cannot be named multiple times.")));
+                return Err(Error::new(ErrorKind::Syntax,
+                                      "&rest cannot be named multiple times."));
             }
             break;
         } else {
             required.push(param.clone());
         }
     }
-    Ok((name, Parameters {
+    Ok(Parameters {
         required, optional, rest,
-    }))
+    })
 }
 
-pub fn define_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let value = scib.unbind("_define-value").unwrap().unwrap_list().clone();
-    match *scib.unbind("_define-name").unwrap() {
+pub fn define_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let value = args[1..].to_vec();
+    match *args[0] {
         Value::Label(ref name) => {
-            let value = try!(progn(scib, &value));
-            scib.set(name.clone(), value.clone());
-            Ok(value)
+            let value = progn_value(scib, &value)?;
+            scib.define(name.clone(), value.clone());
+            Ok(Rc::new(Value::Quote(value)))
         },
-        Value::List(ref l) => {
-            let (name, params) = try!(define_parse_params(l));
+        Value::Pair(_) => {
+            let (name, params) = define_parse_params(&args[0].as_list()?)?;
             let value = Rc::new(Value::Function(Rc::new(
                 Function {
                     params,
                     body: Body::Lisp(value),
+                    env: scib.environment(),
                 })));
-            scib.set(name, value.clone());
+            scib.define(name, value.clone());
             Ok(value)
         },
-        _ => Err(Error::new(ErrorKind::InvalidInput,
-                            format!("Definition name must be a list or label."))),
+        _ => Err(Error::new(ErrorKind::Syntax,
+                            "Definition name must be a list or label.")),
     }
 }
 
-pub fn defmacro_f(scib: &mut Scib) -> Result<Rc<Value>> {
-    let value = scib.unbind("_defmacro-value").unwrap().unwrap_list().clone();
-    match *scib.unbind("_defmacro-name").unwrap() {
-        Value::List(ref l) => {
-            let (name, params) = try!(define_parse_params(l));
+pub fn defmacro_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let value = args[1..].to_vec();
+    match args[0].list_elements() {
+        Some(l) => {
+            let (name, params) = define_parse_params(&l)?;
             let value = Rc::new(Value::Macro(Rc::new(
                 Macro {
                     params,
                     body: Body::Lisp(value),
+                    env: scib.environment(),
                 })));
             scib.set(name, value.clone());
             Ok(value)
         },
-        _ => Err(Error::new(ErrorKind::InvalidInput,
-                            format!("Macro parameters must be a list."))),
+        None => Err(Error::new(ErrorKind::Syntax,
+                               "Macro parameters must be a list.")),
+    }
+}
+
+pub fn lambda_f(scib: &mut Scib, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let value = args[1..].to_vec();
+    match args[0].list_elements() {
+        Some(l) => {
+            let params = parse_params(l.iter())?;
+            Ok(Rc::new(Value::Function(Rc::new(
+                Function {
+                    params,
+                    body: Body::Lisp(value),
+                    env: scib.environment(),
+                }))))
+        },
+        None => Err(Error::new(ErrorKind::Syntax,
+                               "Lambda parameters must be a list.")),
     }
 }
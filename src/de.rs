@@ -0,0 +1,400 @@
+//! Converts `Value`s into Rust data with serde.
+//!
+//! This accepts everything `ser` produces.  In addition, maps and structs
+//! may be written as either association lists of `(key value)` or
+//! `(key . value)` entries or keyword plists such as `(:name "scib" :version 1)`, strings may be
+//! written as labels, and `nil` is accepted as an empty list or map.
+
+use error::{Error, ErrorKind, Result};
+use reader::read_str;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Unexpected, Visitor};
+use std::fmt::Display;
+use std::rc::Rc;
+use std::vec;
+use types::Value;
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new(ErrorKind::Type, msg.to_string())
+    }
+}
+
+/// Converts `value` into a `T`.  Lists are built from pairs whose elements
+/// can't be borrowed, so `T` must own its data.
+pub fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T> {
+    T::deserialize(value)
+}
+
+/// Reads the single datum in `s` and converts it into a `T`.
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T> {
+    let values = read_str(s)?;
+    if values.len() != 1 {
+        return Err(Error::new(ErrorKind::Parse,
+                              format!("Expected exactly one datum, found {}", values.len())));
+    }
+    from_value(&values[0])
+}
+
+fn unexpected(v: &Value) -> Unexpected<'_> {
+    match *v {
+        Value::Nil => Unexpected::Unit,
+        Value::True => Unexpected::Bool(true),
+        Value::Integer(i) => Unexpected::Signed(i),
+        Value::Number(n) => Unexpected::Float(n),
+        Value::Char(c) => Unexpected::Char(c),
+        Value::String(ref s) => Unexpected::Str(s),
+        Value::Pair(_) if v.list_elements().is_some() => Unexpected::Seq,
+        Value::Pair(_) => Unexpected::Other("pair"),
+        Value::BigInt(_) | Value::Rational(_) => Unexpected::Other("exact number"),
+        Value::Label(_) => Unexpected::Other("label"),
+        Value::Function(_) => Unexpected::Other("function"),
+        Value::Macro(_) => Unexpected::Other("macro"),
+        _ => Unexpected::Other("quoted value"),
+    }
+}
+
+fn is_keyword(v: &Value) -> bool {
+    match *v {
+        Value::Label(ref l) => l.len() > 1 && l.starts_with(':'),
+        _ => false,
+    }
+}
+
+fn visit_seq<'de, V: Visitor<'de>>(items: Vec<Rc<Value>>, visitor: V) -> Result<V::Value> {
+    let len = items.len();
+    let mut seq = SeqDeserializer(items.into_iter());
+    let value = visitor.visit_seq(&mut seq)?;
+    match seq.0.len() {
+        0 => Ok(value),
+        n => Err(de::Error::invalid_length(len, &format!("{} fewer elements", n).as_str())),
+    }
+}
+
+fn visit_map<'de, V: Visitor<'de>>(v: &Value, items: Vec<Rc<Value>>, visitor: V) -> Result<V::Value> {
+    let alist = items.iter().all(|e| matches!(**e, Value::Pair(_)));
+    let plist = items.len() % 2 == 0 && items.iter().step_by(2).all(|k| is_keyword(k));
+    if !alist && !plist {
+        return Err(de::Error::invalid_type(unexpected(v), &"an association list or keyword plist"));
+    }
+    visitor.visit_map(MapDeserializer { entries: items.into_iter(), plist, value: None })
+}
+
+impl<'de> de::Deserializer<'de> for &Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match *self {
+            Value::Nil => visitor.visit_unit(),
+            Value::True => visitor.visit_bool(true),
+            Value::Integer(i) => visitor.visit_i64(i),
+            Value::BigInt(ref i) => {
+                // Anything that fits an i64 is an Integer, so try the wider types.
+                let s = i.to_string();
+                if let Ok(u) = s.parse() {
+                    visitor.visit_u64(u)
+                } else if let Ok(i) = s.parse() {
+                    visitor.visit_i128(i)
+                } else if let Ok(u) = s.parse() {
+                    visitor.visit_u128(u)
+                } else {
+                    Err(Error::new(ErrorKind::Range, format!("Integer {} is too large", s)))
+                }
+            },
+            Value::Rational(ref r) => visitor.visit_f64(r.to_f64()),
+            Value::Number(n) => visitor.visit_f64(n),
+            Value::Char(c) => visitor.visit_char(c),
+            Value::String(ref s) | Value::Label(ref s) => visitor.visit_str(s),
+            Value::Pair(_) => match self.list_elements() {
+                Some(items) => visit_seq(items, visitor),
+                None => Err(de::Error::invalid_type(unexpected(self), &visitor)),
+            },
+            _ => Err(de::Error::invalid_type(unexpected(self), &visitor)),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match *self {
+            Value::Nil => visitor.visit_bool(false),
+            Value::True => visitor.visit_bool(true),
+            _ => Err(de::Error::invalid_type(unexpected(self), &visitor)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match *self {
+            Value::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match *self {
+            Value::Nil => visitor.visit_unit(),
+            _ => Err(de::Error::invalid_type(unexpected(self), &visitor)),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.list_elements() {
+            Some(items) => visit_seq(items, visitor),
+            None => Err(de::Error::invalid_type(unexpected(self), &visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize,
+                                                 visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.list_elements() {
+            Some(items) => visit_map(self, items, visitor),
+            None => Err(de::Error::invalid_type(unexpected(self), &visitor)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, _fields: &'static [&'static str],
+                                           visitor: V) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str],
+                                         visitor: V) -> Result<V::Value> {
+        match *self {
+            Value::Label(ref l) => visitor.visit_enum(l.as_str().into_deserializer()),
+            Value::Pair(ref p) => match p.cdr().list_elements() {
+                Some(content) if p.car().as_label().is_ok() => {
+                    visitor.visit_enum(EnumDeserializer { variant: p.car(), content, value: self })
+                },
+                _ => Err(de::Error::invalid_type(unexpected(self), &"a list starting with a label")),
+            },
+            _ => Err(de::Error::invalid_type(unexpected(self), &visitor)),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf identifier ignored_any
+    }
+}
+
+struct SeqDeserializer(vec::IntoIter<Rc<Value>>);
+
+impl<'de> de::SeqAccess<'de> for SeqDeserializer {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.0.next() {
+            Some(v) => seed.deserialize(&*v).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+/// Walks an association list, or a plist if `plist` is set.  The value of
+/// an alist entry is its second element if it is a two element list and
+/// its cdr otherwise.  The keywords of a plist are given to the key without
+/// their leading `:`.
+struct MapDeserializer {
+    entries: vec::IntoIter<Rc<Value>>,
+    plist: bool,
+    value: Option<Rc<Value>>,
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let entry = match self.entries.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if self.plist {
+            self.value = self.entries.next();
+            match *entry {
+                Value::Label(ref l) => seed.deserialize(l[1..].into_deserializer()).map(Some),
+                _ => unreachable!(),
+            }
+        } else {
+            let entry = match *entry {
+                Value::Pair(ref p) => p,
+                _ => unreachable!(),
+            };
+            let cdr = entry.cdr();
+            self.value = Some(match *cdr {
+                Value::Pair(ref p) if *p.cdr() == Value::Nil => p.car(),
+                _ => cdr.clone(),
+            });
+            seed.deserialize(&*entry.car()).map(Some)
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&*self.value.take().expect("next_value_seed called before next_key_seed"))
+    }
+}
+
+/// An enum variant written as a list starting with the variant's label.
+struct EnumDeserializer<'a> {
+    variant: Rc<Value>,
+    content: Vec<Rc<Value>>,
+    value: &'a Value,
+}
+
+impl<'a, 'de> de::EnumAccess<'de> for EnumDeserializer<'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        Ok((seed.deserialize(&*self.variant)?, self))
+    }
+}
+
+impl<'a, 'de> de::VariantAccess<'de> for EnumDeserializer<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.content.len() {
+            0 => Ok(()),
+            _ => Err(de::Error::invalid_type(unexpected(self.value), &"a unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(mut self, seed: T) -> Result<T::Value> {
+        match self.content.len() {
+            1 => seed.deserialize(&*self.content.remove(0)),
+            n => Err(de::Error::invalid_length(n, &"a newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        visit_seq(self.content, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        visit_map(self.value, self.content, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ser::to_value;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        name: String,
+        port: u16,
+        debug: bool,
+        tags: Vec<String>,
+        mode: Mode,
+        limit: Option<u64>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Mode {
+        Fast,
+        Limit(u32),
+        Range(i32, i32),
+        Named { level: u8 },
+    }
+
+    #[test]
+    fn test_from_str_plist() {
+        let config: Config = from_str("(:name \"scib\" :port 8080 :debug t :tags (a \"b\") :mode Fast :limit nil)")
+            .unwrap();
+        assert_eq!(Config {
+            name: "scib".to_string(),
+            port: 8080,
+            debug: true,
+            tags: vec!["a".to_string(), "b".to_string()],
+            mode: Mode::Fast,
+            limit: None,
+        }, config);
+    }
+
+    #[test]
+    fn test_from_str_alist() {
+        let config: Config = from_str("((name scib) (port 1) (debug nil) (tags ()) (mode (Limit 3)) (limit 9))")
+            .unwrap();
+        assert_eq!("scib", config.name);
+        assert!(!config.debug);
+        assert!(config.tags.is_empty());
+        assert_eq!(Mode::Limit(3), config.mode);
+        assert_eq!(Some(9), config.limit);
+        let map: BTreeMap<String, f64> = from_str("((\"a\" 1/2) (b 2.5))").unwrap();
+        assert_eq!(Some(&0.5), map.get("a"));
+        assert_eq!(Some(&2.5), map.get("b"));
+    }
+
+    #[test]
+    fn test_from_str_dotted_alist() {
+        let map: BTreeMap<String, i32> = from_str("((a . 1) (b . 2))").unwrap();
+        assert_eq!(Some(&1), map.get("a"));
+        assert_eq!(Some(&2), map.get("b"));
+        let map: BTreeMap<String, Vec<i32>> = from_str("((a . (1 2 3)) (b (4)) (c . nil))").unwrap();
+        assert_eq!(Some(&vec![1, 2, 3]), map.get("a"));
+        assert_eq!(Some(&vec![4]), map.get("b"));
+        assert_eq!(Some(&vec![]), map.get("c"));
+    }
+
+    #[test]
+    fn test_from_value_cons_list() {
+        let n = |i| Rc::new(Value::Integer(i));
+        let list = Value::cons(n(1), Value::cons(n(2), Rc::new(Value::Nil)));
+        assert_eq!(vec![1, 2], from_value::<Vec<i32>>(&list).unwrap());
+        assert_eq!((1, 2), from_value::<(i32, i32)>(&list).unwrap());
+        assert_eq!(ErrorKind::Type, from_value::<Vec<i32>>(&Value::cons(n(1), n(2))).unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_from_str_variants() {
+        assert_eq!(Mode::Range(-1, 1), from_str::<Mode>("(Range -1 1)").unwrap());
+        assert_eq!(Mode::Named { level: 2 }, from_str::<Mode>("(Named :level 2)").unwrap());
+        assert_eq!(Mode::Named { level: 2 }, from_str::<Mode>("(Named (level 2))").unwrap());
+        assert!(from_str::<Mode>("Slow").is_err());
+        assert!(from_str::<Mode>("(Limit 1 2)").is_err());
+    }
+
+    #[test]
+    fn test_from_str_errors() {
+        assert_eq!(ErrorKind::Type, from_str::<u8>("256").unwrap_err().kind);
+        assert_eq!(ErrorKind::Type, from_str::<String>("1").unwrap_err().kind);
+        assert_eq!(ErrorKind::Type, from_str::<(i32, i32)>("(1 2 3)").unwrap_err().kind);
+        assert_eq!(ErrorKind::Type, from_str::<BTreeMap<String, i32>>("(a 1 b)").unwrap_err().kind);
+        assert_eq!(ErrorKind::Parse, from_str::<i32>("1 2").unwrap_err().kind);
+        assert_eq!(ErrorKind::Incomplete, from_str::<i32>("(1").unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = Config {
+            name: "λ \"quoted\"".to_string(),
+            port: 65535,
+            debug: false,
+            tags: vec![],
+            mode: Mode::Named { level: 7 },
+            limit: Some(u64::MAX),
+        };
+        let value = to_value(&config).unwrap();
+        assert_eq!(config, from_value(&value).unwrap());
+        assert_eq!(config, from_str(&value.to_string()).unwrap());
+    }
+}
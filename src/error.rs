@@ -0,0 +1,120 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+use std::result;
+use types::Value;
+
+pub type Result<T> = result::Result<T, Error>;
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorKind {
+    /// The source text could not be split into tokens.
+    Lex,
+    /// The tokens do not form well balanced expressions.
+    Parse,
+    /// The input ended partway through a datum.  Unlike `Lex` and `Parse`,
+    /// more input could fix this, so a REPL should read another line.
+    Incomplete,
+    /// A special form such as `let` or `define` was used incorrectly.
+    Syntax,
+    /// A label was looked up that has no binding.
+    UnboundVariable(String),
+    /// A function or macro was called with the wrong number of arguments.
+    Arity,
+    /// A value of the wrong type was given.
+    Type,
+    /// A value was of the right type but outside the range accepted, such
+    /// as an index past the end of a string.
+    Range,
+    /// A number was divided by zero.
+    DivisionByZero,
+    /// An error raised from Lisp code by `error`.
+    User(Rc<Value>),
+    /// Reading the source failed.
+    Io,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub file: Option<Rc<str>>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.file {
+            Some(ref file) => write!(f, "{}:{}:{}", file, self.line, self.column),
+            None => write!(f, "{}:{}", self.line, self.column),
+        }
+    }
+}
+
+/// A Lisp function or macro call that was active when an error was raised.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub name: String,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub location: Option<Location>,
+    /// The calls active when the error was raised, innermost first.
+    pub backtrace: Vec<Frame>,
+}
+
+impl Error {
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S) -> Self {
+        Error {
+            kind,
+            message: message.into(),
+            location: None,
+            backtrace: Vec::new(),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Sets the location of the error if it doesn't already have one.
+    pub fn at(mut self, location: Location) -> Self {
+        if self.location.is_none() {
+            self.location = Some(location);
+        }
+        self
+    }
+
+    pub fn push_frame(mut self, name: String, location: Option<Location>) -> Self {
+        self.backtrace.push(Frame { name, location });
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref location) = self.location {
+            write!(f, "{}: ", location)?;
+        }
+        write!(f, "{}", self.message)?;
+        for frame in &self.backtrace {
+            write!(f, "\n  in {}", frame.name)?;
+            if let Some(ref location) = frame.location {
+                write!(f, " at {}", location)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::new(ErrorKind::Io, e.to_string())
+    }
+}
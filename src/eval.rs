@@ -2,100 +2,210 @@ use types::*;
 use instance::*;
 use builtins::*;
 use std::rc::Rc;
-use std::io::{Result, Error, ErrorKind};
+use error::{Result, Error, ErrorKind, Location};
 
-fn eval_function(scib: &mut Scib, f: &Function, unevaled_args: &Vec<Rc<Value>>) -> Result<Rc<Value>> {
+fn eval_function(scib: &mut Scib, f: &Function, first: Rc<Value>, unevaled_args: &[Rc<Value>], location: Option<Location>, output: &mut Vec<Rc<Value>>) -> Result<()> {
+    let mut evaled_args = Vec::with_capacity(unevaled_args.len());
+    evaled_args.push(first);
+    for unevaled_arg in &unevaled_args[1..] {
+        eval(scib, unevaled_arg, &mut evaled_args)?;
+    }
+    apply_function(scib, f, evaled_args, output)
+        .map_err(|e| e.push_frame(call_name(&unevaled_args[0]), location))
+}
+
+fn apply_function(scib: &mut Scib, f: &Function, evaled_args: Vec<Rc<Value>>, output: &mut Vec<Rc<Value>>) -> Result<()> {
+    f.params.check_params_len(evaled_args.len())?;
+    match f.body {
+        Body::Lisp(ref body) =>
+            let_vars(scib, f.env.clone(), f.params.bind_params(evaled_args.into_iter()).into_iter(), body, output),
+        Body::Rust(ref rust) => {
+            output.push(rust(scib, &evaled_args[1..])?);
+            Ok(())
+        },
+    }
 }
 
-fn eval_macro(scib: &mut Scib, m: &Macro, unevaled_args: &Vec<Rc<Value>>) -> Result<Rc<Value>> {
+/// Calls `function` with arguments that have already been evaluated,
+/// returning the last value it produces or `nil` if it produces none.
+/// There is no call form, so errors get an anonymous frame with no
+/// location.
+pub fn call(scib: &mut Scib, function: &Rc<Value>, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+    let f = match **function {
+        Value::Function(ref f) => f,
+        _ => return Err(Error::new(ErrorKind::Type,
+                                   format!("Expected function, found '{:?}'", function))),
+    };
+    let mut evaled_args = Vec::with_capacity(args.len() + 1);
+    evaled_args.push(function.clone());
+    evaled_args.extend(args.iter().cloned());
+    let mut output = Vec::with_capacity(1);
+    apply_function(scib, f, evaled_args, &mut output)
+        .map_err(|e| e.push_frame(call_name(function), None))?;
+    Ok(output.pop().unwrap_or_else(|| Rc::new(Value::Nil)))
 }
 
-fn eval_function_or_macro(scib: &mut Scib, unevaled_args: &Vec<Rc<Value>>, output: &mut Vec<Rc<Value>>) -> Result<()> {
-    let o = Vec::with_capacity(1);
-    try!(eval(scib, &unevaled_args[0], &mut o));
-    let first = o.remove(0);
-    if !o.is_empty() {
-        
+fn expand_macro(scib: &mut Scib, m: &Macro, unevaled_args: &[Rc<Value>], expansion: &mut Vec<Rc<Value>>) -> Result<()> {
+    m.params.check_params_len(unevaled_args.len())?;
+    match m.body {
+        Body::Lisp(ref body) =>
+            let_vars(scib, m.env.clone(), m.params.bind_params(unevaled_args.iter().cloned()).into_iter(), body, expansion),
+        Body::Rust(ref rust) => {
+            expansion.push(rust(scib, &unevaled_args[1..])?);
+            Ok(())
+        },
     }
+}
+
+fn eval_macro(scib: &mut Scib, m: &Macro, unevaled_args: &[Rc<Value>], location: Option<Location>, output: &mut Vec<Rc<Value>>) -> Result<()> {
+    let mut expansion = Vec::with_capacity(1);
+    expand_macro(scib, m, unevaled_args, &mut expansion)
+        .map_err(|e| e.push_frame(call_name(&unevaled_args[0]), location))?;
+    for e in expansion {
+        eval(scib, &e, output)?;
+    }
+    Ok(())
+}
+
+fn call_name(head: &Value) -> String {
+    match *head {
+        Value::Label(ref l) => l.clone(),
+        _ => String::from("<anonymous>"),
+    }
+}
+
+fn eval_function_or_macro(scib: &mut Scib, form: &Rc<Value>, unevaled_args: &[Rc<Value>], output: &mut Vec<Rc<Value>>) -> Result<()> {
+    let first = eval_value(scib, &unevaled_args[0])?;
+    let location = scib.location(form);
     match *first {
-        Value::Function(ref f) => {
-            try!(f.params.check_params_len(unevaled_args.len()));
-            let mut evaled_args = Vec::with_capacity(unevaled_args.len());
-            for unevaled_arg in unevaled_args {
-                evaled_args.push(try!(eval(scib, unevaled_arg)));
-            }
-            let_vars(scib, f.params.bind_params(evaled_args.into_iter()).into_iter(), &f.body)
-        },
-        Value::Macro(ref m) => {
-            try!(m.params.check_params_len(unevaled_args.len()));
-            let result = try!(let_vars(scib, m.params.bind_params(unevaled_args.iter().cloned()).into_iter(), &m.body));
-            eval(scib, &result)
+        Value::Function(ref f) => eval_function(scib, f, first.clone(), unevaled_args, location, output),
+        Value::Macro(ref m) => eval_macro(scib, m, unevaled_args, location, output),
+        _ => Err(Error::new(ErrorKind::Type,
+                            format!("Expected function or macro, found '{:?}'", first))),
+    }
+}
+
+fn splice(v: &Rc<Value>, output: &mut Vec<Rc<Value>>) -> Result<()> {
+    match v.list_elements() {
+        Some(l) => {
+            output.extend(l);
+            Ok(())
         },
-        _ => panic!(),
+        None => Err(Error::new(ErrorKind::Type,
+                               format!("Unquote list requires a list to splice, found '{:?}'", v))),
     }
 }
 
-pub fn eval_backquote(scib: &mut Scib, v: &Rc<Value>, in_backquote: i32) -> Result<Rc<Value>> {
+/// Backquotes each car of the pairs starting at `pair` and then the final
+/// cdr, rebuilding the chain from the results.
+fn eval_backquote_pair(scib: &mut Scib, pair: &Pair, in_backquote: i32, output: &mut Vec<Rc<Value>>) -> Result<()> {
+    let mut cars = Vec::new();
+    eval_backquote(scib, &pair.car(), in_backquote, &mut cars)?;
+    let mut tail = pair.cdr();
+    while let Value::Pair(ref p) = *tail.clone() {
+        eval_backquote(scib, &p.car(), in_backquote, &mut cars)?;
+        tail = p.cdr();
+    }
+    let mut o = Vec::with_capacity(1);
+    eval_backquote(scib, &tail, in_backquote, &mut o)?;
+    if o.len() != 1 {
+        return Err(Error::new(ErrorKind::Syntax,
+                              format!("Expected exactly one value after a dot, found {}", o.len())));
+    }
+    output.push(cars.into_iter().rev().fold(o.pop().unwrap(), |cdr, car| Value::cons(car, cdr)));
+    Ok(())
+}
+
+pub fn eval_backquote(scib: &mut Scib, v: &Rc<Value>, in_backquote: i32, output: &mut Vec<Rc<Value>>) -> Result<()> {
     match **v {
         Value::True |
         Value::Nil |
+        Value::Integer(_) |
+        Value::BigInt(_) |
+        Value::Rational(_) |
         Value::Number(_) |
+        Value::Char(_) |
         Value::String(_) |
         Value::Function(_) |
         Value::Macro(_) |
-        Value::Label(_) => Ok(v.clone()),
+        Value::Label(_) => output.push(v.clone()),
         Value::Unquote(ref v) => {
             if in_backquote == 1 {
-                eval(scib, v)
+                eval(scib, v, output)?;
             } else {
-                eval_backquote(scib, v, in_backquote - 1)
+                let mut o = Vec::with_capacity(1);
+                eval_backquote(scib, v, in_backquote - 1, &mut o)?;
+                output.extend(o.into_iter().map(|v| Rc::new(Value::Unquote(v))));
             }
         },
         Value::UnquoteList(ref v) => {
+            let mut o = Vec::with_capacity(1);
             if in_backquote == 1 {
-                eval(scib, v)
+                eval(scib, v, &mut o)?;
+                for v in o {
+                    splice(&v, output)?;
+                }
+            } else {
+                eval_backquote(scib, v, in_backquote - 1, &mut o)?;
+                output.extend(o.into_iter().map(|v| Rc::new(Value::UnquoteList(v))));
             }
         },
-        Value::List(ref list) => {
-            Ok(Rc::new(Value::List(try!(list.iter().map(
-                |v| eval_backquote(scib, v, in_backquote))
-                                   .collect()))))
-        },
+        Value::Pair(ref p) => eval_backquote_pair(scib, p, in_backquote, output)?,
         Value::Quote(ref v) => {
-            Ok(Rc::new(Value::Quote(try!(eval_backquote(scib, v, in_backquote)))))
+            let mut o = Vec::with_capacity(1);
+            eval_backquote(scib, v, in_backquote, &mut o)?;
+            output.extend(o.into_iter().map(|v| Rc::new(Value::Quote(v))));
         },
         Value::Backquote(ref v) => {
-            Ok(Rc::new(Value::Quote(try!(eval_backquote(scib, v, in_backquote + 1)))))
-        }
+            let mut o = Vec::with_capacity(1);
+            eval_backquote(scib, v, in_backquote + 1, &mut o)?;
+            output.extend(o.into_iter().map(|v| Rc::new(Value::Backquote(v))));
+        },
     }
+    Ok(())
 }
 
+/// Evaluates `v`, pushing the values it produces onto `output`.  Errors
+/// without a location are given the location of `v` if it was read from
+/// source text.
 pub fn eval(scib: &mut Scib, v: &Rc<Value>, output: &mut Vec<Rc<Value>>) -> Result<()> {
+    eval_(scib, v, output).map_err(|e| match scib.location(v) {
+        Some(location) => e.at(location),
+        None => e,
+    })
+}
+
+fn eval_(scib: &mut Scib, v: &Rc<Value>, output: &mut Vec<Rc<Value>>) -> Result<()> {
     output.push(match **v {
         Value::True |
         Value::Nil |
+        Value::Integer(_) |
+        Value::BigInt(_) |
+        Value::Rational(_) |
         Value::Number(_) |
+        Value::Char(_) |
         Value::String(_) |
         Value::Function(_) |
         Value::Macro(_) => {
             v.clone()
         },
         Value::Label(ref label) => {
-            try!(scib.lookup(label))
+            scib.lookup(label)?
         },
-        Value::List(ref list) => {
-            if list.is_empty() {
-                Rc::new(Value::Nil)
-            } else {
-                return eval_function_or_macro(scib, list, output)
+        Value::Pair(_) => {
+            return match v.list_elements() {
+                Some(list) => eval_function_or_macro(scib, v, &list, output),
+                None => Err(Error::new(ErrorKind::Syntax,
+                                       format!("Cannot evaluate the improper list '{}'", v))),
             }
         },
         Value::Backquote(ref v) => {
-            try!(eval_backquote(scib, v, 1))
+            return eval_backquote(scib, v, 1, output)
         },
-        Value::Unquote(ref v) => {
-            Err(Error::new(ErrorKind::InvalidInput,
-                           format!("Unquote without accompanying backquote")))
+        Value::Unquote(_) |
+        Value::UnquoteList(_) => {
+            return Err(Error::new(ErrorKind::Syntax,
+                                  "Unquote without accompanying backquote"))
         },
         Value::Quote(ref v) => {
             v.clone()
@@ -104,46 +214,66 @@ pub fn eval(scib: &mut Scib, v: &Rc<Value>, output: &mut Vec<Rc<Value>>) -> Resu
     Ok(())
 }
 
+/// Evaluates `v`, requiring that it produce exactly one value.
+pub fn eval_value(scib: &mut Scib, v: &Rc<Value>) -> Result<Rc<Value>> {
+    let mut output = Vec::with_capacity(1);
+    eval(scib, v, &mut output)?;
+    if output.len() == 1 {
+        Ok(output.remove(0))
+    } else {
+        Err(Error::new(ErrorKind::Arity,
+                       format!("Expected exactly one value from '{:?}', found {}", v, output.len())))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use lex::lex;
+    use lex::Lexer;
+    use reader::Reader;
     use parse::parse;
+    use source_map::SourceMap;
 
     fn unwrap_1<T>(mut v: Vec<T>) -> T {
         assert!(v.len() == 1);
         v.remove(0)
     }
 
+    fn eval_1(instance: &mut Scib, s: &str) -> Rc<Value> {
+        let mut output = Vec::new();
+        let mut map = SourceMap::new();
+        eval(instance, &unwrap_1(parse(Lexer::new(s.chars(), None), &mut map).unwrap()), &mut output).unwrap();
+        unwrap_1(output)
+    }
+
+    fn list(v: Vec<Value>) -> Value {
+        Rc::try_unwrap(Value::list(v.into_iter().map(Rc::new).collect::<Vec<_>>())).unwrap()
+    }
+
+    fn label(s: &str) -> Value {
+        Value::Label(s.to_owned())
+    }
+
     #[test]
     fn test_eval_1() {
         let mut instance = Scib::new();
-        assert_eq!(Value::Number(123.0),
-                   *eval(&mut instance,
-                         &unwrap_1(parse(lex("123".chars().fuse()).unwrap())
-                                   .unwrap()))
-                   .unwrap());
+        assert_eq!(Value::Integer(123),
+                   *eval_1(&mut instance, "123"));
     }
 
     #[test]
     fn test_eval_2() {
         let mut instance = Scib::new();
         assert_eq!(Value::String(String::from("HI")),
-                   *eval(&mut instance,
-                         &unwrap_1(parse(lex("\"HI\"".chars().fuse()).unwrap())
-                                   .unwrap()))
-                   .unwrap());
+                   *eval_1(&mut instance, "\"HI\""));
     }
 
     #[test]
     fn test_eval_3() {
         let mut instance = Scib::new();
-        assert_eq!(Value::Number(123.0),
-                   *eval(&mut instance,
-                         &unwrap_1(parse(lex("(setq xo 123)".chars().fuse()).unwrap())
-                                   .unwrap()))
-                   .unwrap());
-        assert_eq!(Value::Number(123.0),
+        assert_eq!(Value::Integer(123),
+                   *eval_1(&mut instance, "(setq xo 123)"));
+        assert_eq!(Value::Integer(123),
                    **instance.get("xo").unwrap());
     }
 
@@ -151,10 +281,7 @@ mod tests {
     fn test_eval_4() {
         let mut instance = Scib::new();
         assert_eq!(Value::Label(String::from("y")),
-                   *eval(&mut instance,
-                         &unwrap_1(parse(lex("(setq x 'y)".chars().fuse()).unwrap())
-                                   .unwrap()))
-                   .unwrap());
+                   *eval_1(&mut instance, "(setq x 'y)"));
         assert_eq!(Value::Label(String::from("y")),
                    **instance.get("x").unwrap());
     }
@@ -162,14 +289,14 @@ mod tests {
     #[test]
     fn test_eval_5() {
         let mut instance = Scib::new();
-        assert_eq!(Value::List(vec![Rc::new(Value::Number(1.0)), Rc::new(Value::Number(3.0))]),
+        assert_eq!(list(vec![Value::Integer(1), Value::Integer(3)]),
                    *instance.eval("(setq x 2)(setq y 3)(setq x 1)(list x y)").unwrap());
     }
 
     #[test]
     fn test_eval_6() {
         let mut instance = Scib::new();
-        assert_eq!(Value::Number(7.0),
+        assert_eq!(Value::Integer(7),
                    *instance.eval("(progn(setq x 1)(setq y 2)(setq z 3)(+ x (* y z)))").unwrap());
     }
 
@@ -183,21 +310,22 @@ mod tests {
                 rest: None,
             },
             body: Body::Lisp(vec![
-                Rc::new(Value::List(vec![
+                Value::list(vec![
                     Rc::new(Value::Label(String::from("+"))),
-                    Rc::new(Value::Number(1.0)),
+                    Rc::new(Value::Integer(1)),
                     Rc::new(Value::Label(String::from("x"))),
-                ]))]),
+                ])]),
+            env: None,
         })),
                    *instance.eval("(define (f x) (+ 1 x))").unwrap());
-        assert_eq!(Value::Number(23.0),
+        assert_eq!(Value::Integer(23),
                    *instance.eval("(f 22)").unwrap());
     }
 
     #[test]
     fn test_eval_8() {
         let mut instance = Scib::new();
-        assert_eq!(Value::Number(13.0),
+        assert_eq!(Value::Integer(13),
                    *instance.eval("(- (/ 30 2 3) -8)").unwrap());
     }
 
@@ -207,4 +335,606 @@ mod tests {
         assert_eq!(Value::Nil,
                    *instance.eval("(when (= 1 3) 13 23)").unwrap());
     }
+
+    #[test]
+    fn test_eval_backquote_1() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![label("a"), Value::Integer(3), label("c")]),
+                   *instance.eval("`(a ,(+ 1 2) c)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_backquote_splice_start() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![Value::Integer(1), Value::Integer(2), label("c")]),
+                   *instance.eval("(setq x '(1 2)) `(,@x c)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_backquote_splice_middle() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![label("a"), Value::Integer(1), Value::Integer(2), label("c")]),
+                   *instance.eval("(setq x '(1 2)) `(a ,@x c)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_backquote_splice_end() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![label("a"), Value::Integer(1), Value::Integer(2)]),
+                   *instance.eval("(setq x '(1 2)) `(a ,@x)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_backquote_splice_nil() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![label("a"), label("c")]),
+                   *instance.eval("`(a ,@nil c)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_backquote_splice_non_list() {
+        let mut instance = Scib::new();
+        assert!(instance.eval("`(a ,@1 c)").is_err());
+    }
+
+    #[test]
+    fn test_eval_backquote_nested() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![
+            label("a"),
+            Value::Backquote(Rc::new(list(vec![
+                label("b"),
+                Value::Unquote(Rc::new(list(vec![label("c"), Value::Integer(3)]))),
+                Value::UnquoteList(Rc::new(label("d"))),
+            ])))]),
+                   *instance.eval("(setq x 3) `(a `(b ,(c ,x) ,@d))").unwrap());
+    }
+
+    #[test]
+    fn test_eval_backquote_nested_splice() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![
+            Value::Backquote(Rc::new(list(vec![
+                Value::Unquote(Rc::new(list(vec![label("f"), Value::Integer(1), Value::Integer(2)]))),
+            ])))]),
+                   *instance.eval("(setq x '(1 2)) `(`(,(f ,@x)))").unwrap());
+    }
+
+    #[test]
+    fn test_eval_splice_into_arguments() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![Value::Integer(0), Value::Integer(1), Value::Integer(2), Value::Integer(3)]),
+                   *instance.eval("(setq x '(1 2)) (list 0 `,@x 3)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_when() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Integer(23),
+                   *instance.eval("(when (= 1 1) 13 23)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_let() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Integer(4),
+                   *instance.eval("(setq x 1) (let ((x 2) (y (+ x 2))) (+ x y 1 -2))").unwrap());
+        assert_eq!(Value::Integer(1),
+                   **instance.get("x").unwrap());
+    }
+
+    #[test]
+    fn test_eval_lexical_scope() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Integer(1),
+                   *instance.eval("(setq x 1) (define (f) x) (define (g x) (f)) (g 2)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_setq_local() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Integer(3),
+                   *instance.eval("(setq x 1) (define (f x) (setq x 3) x) (f 2)").unwrap());
+        assert_eq!(Value::Integer(1),
+                   **instance.get("x").unwrap());
+    }
+
+    #[test]
+    fn test_eval_error_unwinds_bindings() {
+        let mut instance = Scib::new();
+        assert!(instance.eval("(define (f x) (undefined x)) (f 2)").is_err());
+        assert!(instance.get("x").is_none());
+        assert!(instance.eval("x").is_err());
+    }
+
+    #[test]
+    fn test_eval_closure() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![Value::Integer(3), Value::Integer(6)]),
+                   *instance.eval("(define (make-adder n) (define (adder x) (+ x n)) adder)
+                                   (setq add2 (make-adder 2))
+                                   (setq add5 (make-adder 5))
+                                   (list (add2 1) (add5 1))").unwrap());
+    }
+
+    #[test]
+    fn test_eval_internal_define() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![Value::Integer(1), Value::Integer(2), Value::Integer(1)]),
+                   *instance.eval("(define (make-counter) (define count 0) (lambda () (setq count (+ count 1)) count))
+                                   (setq c1 (make-counter))
+                                   (setq c2 (make-counter))
+                                   (list (c1) (c1) (c2))").unwrap());
+        assert_eq!(Value::Integer(5), *instance.eval("(define (f) (define tmp 5) tmp) (f)").unwrap());
+        assert_eq!(ErrorKind::UnboundVariable("tmp".to_owned()), instance.eval("tmp").unwrap_err().kind);
+        assert_eq!(ErrorKind::UnboundVariable("y".to_owned()),
+                   instance.eval("(let ((x 1)) (define y 2)) y").unwrap_err().kind);
+        assert_eq!(ErrorKind::UnboundVariable("count".to_owned()), instance.eval("count").unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_eval_closure_equality() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Nil,
+                   *instance.eval("(define (make-adder n) (define (adder x) (+ x n)) adder)
+                                   (equal (make-adder 2) (make-adder 2))").unwrap());
+        assert_eq!(Value::True,
+                   *instance.eval("(setq add2 (make-adder 2)) (equal add2 add2)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_lambda() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Integer(5),
+                   *instance.eval("((lambda (x &optional y) (+ x 1)) 4)").unwrap());
+        assert_eq!(list(vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)]),
+                   *instance.eval("((lambda (&rest r) r) 1 2 3)").unwrap());
+        assert_eq!(Value::Integer(7),
+                   *instance.eval("((lambda () 7))").unwrap());
+    }
+
+    #[test]
+    fn test_eval_lambda_closure() {
+        let mut instance = Scib::new();
+        assert_eq!(list(vec![Value::Integer(3), Value::Integer(6)]),
+                   *instance.eval("(define (make-adder n) (lambda (x) (+ x n)))
+                                   (define (call f x) (f x))
+                                   (list (call (make-adder 2) 1) ((make-adder 5) 1))").unwrap());
+    }
+
+    #[test]
+    fn test_eval_lambda_invalid_params() {
+        let mut instance = Scib::new();
+        assert!(instance.eval("(lambda x x)").is_err());
+        assert!(instance.eval("(lambda (&rest) x)").is_err());
+    }
+
+    #[test]
+    fn test_eval_register_fn_closure() {
+        use std::cell::Cell;
+        let mut instance = Scib::new();
+        let counter = Rc::new(Cell::new(0));
+        let c = counter.clone();
+        instance.register_fn("bump", Parameters::new(&["by"], &[], None), move |_, args| {
+            let by = match *args[0] {
+                Value::Integer(n) => n,
+                _ => panic!(),
+            };
+            c.set(c.get() + by);
+            Ok(Rc::new(Value::Integer(c.get())))
+        });
+        assert_eq!(Value::Integer(5),
+                   *instance.eval("(bump 2) (bump 3)").unwrap());
+        assert_eq!(5, counter.get());
+    }
+
+    #[test]
+    fn test_eval_builtin_names_not_bound() {
+        let mut instance = Scib::new();
+        assert!(instance.eval("(if 1 _if-cond)").is_err());
+        assert_eq!(Value::Integer(3),
+                   *instance.eval("(setq _+ 1) (+ _+ 2)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_builtin_recursion() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Integer(120),
+                   *instance.eval("(define (fact n) (if (= n 0) 1 (* n (fact (- n 1))))) (fact 5)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_error_kinds() {
+        let mut instance = Scib::new();
+        assert_eq!(ErrorKind::UnboundVariable(String::from("nope")),
+                   instance.eval("nope").unwrap_err().kind);
+        assert_eq!(ErrorKind::Arity,
+                   instance.eval("(define (f x) x) (f)").unwrap_err().kind);
+        assert_eq!(ErrorKind::Type,
+                   instance.eval("(+ 1 \"a\")").unwrap_err().kind);
+        assert_eq!(ErrorKind::Syntax,
+                   instance.eval("(let (1) 1)").unwrap_err().kind);
+        assert_eq!(ErrorKind::Parse,
+                   instance.eval("(+ 1))").unwrap_err().kind);
+        assert_eq!(ErrorKind::Lex,
+                   instance.eval("\"\\q\"").unwrap_err().kind);
+        assert_eq!(ErrorKind::Incomplete,
+                   instance.eval("(+ 1").unwrap_err().kind);
+        assert_eq!(ErrorKind::Incomplete,
+                   instance.eval("\"abc").unwrap_err().kind);
+        assert_eq!(ErrorKind::User(Rc::new(Value::String(String::from("oops")))),
+                   instance.eval("(error \"oops\")").unwrap_err().kind);
+        assert_eq!("(bad 1/2 \"x\")", instance.eval("(error '(bad 1/2 \"x\"))").unwrap_err().message);
+    }
+
+    #[test]
+    fn test_eval_error_backtrace() {
+        let mut instance = Scib::new();
+        let e = instance.eval("(define (g x) (+ x y)) (define (f x) (g x)) (f 1)").unwrap_err();
+        assert_eq!(vec!["g", "f"],
+                   e.backtrace.iter().map(|f| f.name.as_str()).collect::<Vec<_>>());
+        let e = instance.eval("(f (+ 1 \"a\"))").unwrap_err();
+        assert_eq!(vec!["+"],
+                   e.backtrace.iter().map(|f| f.name.as_str()).collect::<Vec<_>>());
+        let e = instance.eval("(map (lambda (x) (g x)) '(1))").unwrap_err();
+        assert_eq!(vec!["g", "<anonymous>", "map"],
+                   e.backtrace.iter().map(|f| f.name.as_str()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_eval_error_location() {
+        let mut instance = Scib::new();
+        let e = instance.eval("(define (g x)\n  (+ x y))\n(g 1)").unwrap_err();
+        let loc = |line, column| Some(Location { file: None, line, column });
+        assert_eq!(loc(2, 8), e.location);
+        assert_eq!(loc(3, 1), e.backtrace[0].location);
+        let e = instance.eval("(+ 1\n   \"a\")").unwrap_err();
+        assert_eq!(loc(1, 1), e.location);
+    }
+
+    #[test]
+    fn test_eval_numeric_tower() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Integer(6), *instance.eval("(+ 1 2 3)").unwrap());
+        assert_eq!(Value::Number(6.5), *instance.eval("(+ 1 2 3.5)").unwrap());
+        assert_eq!(Value::Integer(-4), *instance.eval("(- 1 2 3)").unwrap());
+        assert_eq!(Value::Integer(24), *instance.eval("(* 2 3 4)").unwrap());
+        assert_eq!(Value::Integer(5), *instance.eval("(/ 30 2 3)").unwrap());
+        assert_eq!(Value::Number(2.5), *instance.eval("(/ 5.0 2)").unwrap());
+        assert_eq!(Value::BigInt("9223372036854775808".parse().unwrap()),
+                   *instance.eval("(+ 9223372036854775807 1)").unwrap());
+    }
+
+    #[test]
+    fn test_eval_chars() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Integer(65), *instance.eval("(char->integer #\\A)").unwrap());
+        assert_eq!(Value::Char('λ'), *instance.eval("(integer->char 955)").unwrap());
+        assert_eq!(Value::Char('✓'), *instance.eval("(string-ref \"a✓b\" 1)").unwrap());
+        assert_eq!(ErrorKind::Range, instance.eval("(string-ref \"ab\" 2)").unwrap_err().kind);
+        assert_eq!(ErrorKind::Range, instance.eval("(integer->char -1)").unwrap_err().kind);
+        assert_eq!(ErrorKind::Type, instance.eval("(char->integer \"a\")").unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_eval_all_streams() {
+        let mut instance = Scib::new();
+        assert_eq!(ErrorKind::Parse, instance.eval("(define x 1) )").unwrap_err().kind);
+        assert_eq!(Value::Integer(1), *instance.eval("x").unwrap());
+        let mut reader = Reader::new("(+ x 1) (+ x 2)".chars(), None);
+        assert_eq!(Value::Integer(3), *instance.eval_all(&mut reader).unwrap());
+    }
+
+    #[test]
+    fn test_eval_reader_utf8() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Char('λ'),
+                   *instance.eval_reader("(string-ref \"aλb\" 1)".as_bytes()).unwrap());
+        let e = instance.eval_reader(&b"(a\n  \"\xce\xbb\xff\")"[..]).unwrap_err();
+        assert_eq!(ErrorKind::Io, e.kind);
+        assert_eq!(Some(Location { file: None, line: 2, column: 5 }), e.location);
+    }
+
+    #[test]
+    fn test_eval_reader_io_error() {
+        struct Failing;
+        impl ::std::io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> ::std::io::Result<usize> {
+                Err(::std::io::Error::other("disk on fire"))
+            }
+        }
+        let mut instance = Scib::new();
+        assert_eq!(ErrorKind::Io, instance.eval_reader(Failing).unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_eval_reader_streams() {
+        // Gives one datum and then fails, so it is only defined if it was
+        // evaluated before the end of the input.
+        struct FailsAfterFirst(&'static [u8]);
+        impl ::std::io::Read for FailsAfterFirst {
+            fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+                if self.0.is_empty() {
+                    return Err(::std::io::Error::other("connection reset"));
+                }
+                self.0.read(buf)
+            }
+        }
+        let mut instance = Scib::new();
+        let e = instance.eval_reader(FailsAfterFirst(b"(define x 1)\n(+ x")).unwrap_err();
+        assert_eq!(ErrorKind::Io, e.kind);
+        assert_eq!(Some(Location { file: None, line: 2, column: 5 }), e.location);
+        assert_eq!(Value::Integer(1), *instance.eval("x").unwrap());
+    }
+
+    #[test]
+    fn test_eval_file_utf8() {
+        let path = ::std::env::temp_dir().join(format!("scib-test-{}.scib", ::std::process::id()));
+        ::std::fs::write(&path, "; ✓\n(string-ref \"✓λ\" 1)").unwrap();
+        let result = Scib::new().eval_file(path.to_str().unwrap());
+        ::std::fs::remove_file(&path).unwrap();
+        assert_eq!(Value::Char('λ'), *result.unwrap());
+    }
+
+    #[test]
+    fn test_eval_exact_rationals() {
+        let mut instance = Scib::new();
+        assert_eq!("1/3", instance.eval("(/ 1 3)").unwrap().to_string());
+        assert_eq!("1/2", instance.eval("(+ 1/3 1/6)").unwrap().to_string());
+        assert_eq!(Value::Integer(1), *instance.eval("(* 3 1/3)").unwrap());
+        assert_eq!(Value::Number(0.75), *instance.eval("(+ 1/4 0.5)").unwrap());
+        assert_eq!(Value::True, *instance.eval("(= 2/4 1/2)").unwrap());
+        assert_eq!("265252859812191058636308480000000",
+                   instance.eval("(define (fact n) (if (= n 0) 1 (* n (fact (- n 1))))) (fact 30)")
+                   .unwrap().to_string());
+        assert_eq!(Value::Integer(1), *instance.eval("(/ (fact 30) (fact 30))").unwrap());
+    }
+
+    #[test]
+    fn test_eval_pairs() {
+        let mut instance = Scib::new();
+        let eval = |instance: &mut Scib, s: &str| instance.eval(s).unwrap().to_string();
+        assert_eq!("(1 . 2)", eval(&mut instance, "(cons 1 2)"));
+        assert_eq!("(1 2 3)", eval(&mut instance, "(cons 1 '(2 3))"));
+        assert_eq!("1", eval(&mut instance, "(car '(1 . 2))"));
+        assert_eq!("(2 . 3)", eval(&mut instance, "(cdr '(1 2 . 3))"));
+        assert_eq!("(2 3)", eval(&mut instance, "(cdr '(1 2 3))"));
+        assert_eq!("nil", eval(&mut instance, "(cdr '(1))"));
+        assert_eq!("nil", eval(&mut instance, "(car nil)"));
+        // Both lists share the tail, so changing it changes both.
+        instance.eval("(define tail (cons 2 nil)) (define a (cons 1 tail)) (define b (cons 0 tail))").unwrap();
+        instance.eval("(set-car! tail 9) (set-cdr! tail 10)").unwrap();
+        assert_eq!("(1 9 . 10)", eval(&mut instance, "a"));
+        assert_eq!("(0 9 . 10)", eval(&mut instance, "b"));
+        // Every list is made of pairs, and cdr, member and last share them.
+        instance.eval("(define xs (list 1 2 3))").unwrap();
+        instance.eval("(set-car! xs 0) (set-car! (cdr xs) 5) (set-car! (member 3 xs) 7)").unwrap();
+        instance.eval("(set-cdr! (last xs) '(8))").unwrap();
+        assert_eq!("(0 5 7 8)", eval(&mut instance, "xs"));
+        assert_eq!("(9 2)", eval(&mut instance, "(let ((ys '(1 2))) (set-car! ys 9) ys)"));
+        assert_eq!(ErrorKind::Type, instance.eval("(set-car! nil 3)").unwrap_err().kind);
+        // A list made circular with set-cdr! can still be printed and
+        // compared.
+        instance.eval("(define a (cons 1 (cons 2 nil))) (set-cdr! (cdr a) a)").unwrap();
+        instance.eval("(define b (cons 1 (cons 2 nil))) (set-cdr! (cdr b) b)").unwrap();
+        instance.eval("(define c (cons 1 (cons 3 nil))) (set-cdr! (cdr c) c)").unwrap();
+        assert_eq!("(1 2 ...)", eval(&mut instance, "a"));
+        assert_eq!("(t nil)", eval(&mut instance, "(list (equal a b) (equal a c))"));
+        assert_eq!(ErrorKind::Type, instance.eval("(length a)").unwrap_err().kind);
+        assert_eq!(ErrorKind::Type, instance.eval("(car 1)").unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_eval_pair_code() {
+        let mut instance = Scib::new();
+        assert_eq!(Value::Integer(3), *instance.eval("(defmacro (add a b) (cons '+ (cons a (cons b nil)))) (add 1 2)").unwrap());
+        assert_eq!(ErrorKind::Syntax, instance.eval("(defmacro (bad) (cons '+ 1)) (bad)").unwrap_err().kind);
+        assert_eq!("(1 2 . 3)", instance.eval("(let ((x 2) (y '(1))) `(,@y ,x . ,(+ x 1)))").unwrap().to_string());
+        assert_eq!("(0 1 2 3)", instance.eval("`(0 ,@(cons 1 (cons 2 nil)) 3)").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_eval_list_builtins() {
+        let mut instance = Scib::new();
+        let mut eval = |s: &str| instance.eval(s).unwrap().to_string();
+        assert_eq!("3", eval("(length '(a b c))"));
+        assert_eq!("2", eval("(length (cons 1 (cons 2 nil)))"));
+        assert_eq!("0", eval("(length nil)"));
+        assert_eq!("b", eval("(nth 1 '(a b c))"));
+        assert_eq!("nil", eval("(nth 5 '(a b c))"));
+        assert_eq!("c", eval("(list-ref '(a b c) 2)"));
+        assert_eq!("(1 2 3 4)", eval("(append '(1) '(2 3) nil '(4))"));
+        assert_eq!("(1 2 . 3)", eval("(append '(1 2) 3)"));
+        assert_eq!("nil", eval("(append)"));
+        assert_eq!("(c b a)", eval("(reverse '(a b c))"));
+        assert_eq!("(c)", eval("(last '(a b c))"));
+        assert_eq!("(b . c)", eval("(last '(a b . c))"));
+        assert_eq!("nil", eval("(last nil)"));
+        assert_eq!("((1 2) 3)", eval("(member '(1 2) '(0 (1 2) 3))"));
+        assert_eq!("nil", eval("(member 4 '(1 2 3))"));
+        assert_eq!("(b . 2)", eval("(assoc 'b '((a . 1) (b . 2)))"));
+        assert_eq!("(\"b\" 2)", eval("(assoc \"b\" '((\"a\" 1) (\"b\" 2)))"));
+        assert_eq!("nil", eval("(assoc 'c '((a . 1)))"));
+        assert_eq!("t", eval("(null? '())"));
+        assert_eq!("nil", eval("(null? '(nil))"));
+    }
+
+    #[test]
+    fn test_eval_list_sharing() {
+        let mut instance = Scib::new();
+        instance.eval("(define xs (cons 1 (cons 2 nil))) (set-cdr! (last xs) '(3))").unwrap();
+        assert_eq!("(1 2 3)", instance.eval("xs").unwrap().to_string());
+        instance.eval("(define ys (append '(0) xs)) (set-car! (member 2 xs) 9)").unwrap();
+        assert_eq!("(0 1 9 3)", instance.eval("ys").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_eval_list_errors() {
+        let mut instance = Scib::new();
+        let mut kind = |s: &str| instance.eval(s).unwrap_err().kind;
+        assert_eq!(ErrorKind::Type, kind("(length 1)"));
+        assert_eq!(ErrorKind::Type, kind("(length '(1 . 2))"));
+        assert_eq!(ErrorKind::Type, kind("(nth 'a '(1))"));
+        assert_eq!(ErrorKind::Range, kind("(nth -1 '(1))"));
+        assert_eq!(ErrorKind::Range, kind("(list-ref '(a b) 2)"));
+        assert_eq!(ErrorKind::Type, kind("(append 1 '(2))"));
+        assert_eq!(ErrorKind::Type, kind("(member 1 2)"));
+        assert_eq!(ErrorKind::Type, kind("(assoc 'a '(a))"));
+        assert_eq!(ErrorKind::Arity, kind("(reverse)"));
+    }
+
+    #[test]
+    fn test_eval_call() {
+        let mut instance = Scib::new();
+        let square = instance.eval("(lambda (x) (* x x))").unwrap();
+        assert_eq!(Value::Integer(9), *instance.call(&square, &[Rc::new(Value::Integer(3))]).unwrap());
+        let plus = instance.eval("+").unwrap();
+        assert_eq!(Value::Integer(3), *instance.call(&plus, &[Rc::new(Value::Integer(1)), Rc::new(Value::Integer(2))]).unwrap());
+        // Arguments are passed as they are, not evaluated again.
+        let quote = instance.eval("(lambda (x) x)").unwrap();
+        let args = [Rc::new(Value::Label("unbound".to_string()))];
+        assert_eq!(args[0], instance.call(&quote, &args).unwrap());
+        assert_eq!(ErrorKind::Arity, instance.call(&square, &[]).unwrap_err().kind);
+        let when = instance.eval("when").unwrap();
+        assert_eq!(ErrorKind::Type, instance.call(&when, &[]).unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_eval_higher_order() {
+        let mut instance = Scib::new();
+        instance.register_fn("less", Parameters::new(&["a", "b"], &[], None), |_, args| {
+            Ok(Rc::new(if args[0].as_integer()? < args[1].as_integer()? { Value::True } else { Value::Nil }))
+        });
+        let mut eval = |s: &str| instance.eval(s).unwrap().to_string();
+        assert_eq!("6", eval("(funcall + 1 2 3)"));
+        assert_eq!("10", eval("(apply + 1 2 '(3 4))"));
+        assert_eq!("0", eval("(apply +)"));
+        assert_eq!("(1 4 9)", eval("(map (lambda (x) (* x x)) '(1 2 3))"));
+        assert_eq!("(11 22)", eval("(map + '(1 2 3) '(10 20))"));
+        assert_eq!("(1 0)", eval("(filter (lambda (x) (less x 2)) '(1 2 3 0))"));
+        assert_eq!("10", eval("(reduce + '(1 2 3 4))"));
+        assert_eq!("(3 2 1)", eval("(reduce (lambda (acc x) (cons x acc)) '(1 2 3) nil)"));
+        assert_eq!("0", eval("(reduce + nil)"));
+        assert_eq!("(1 2 3 5 8)", eval("(sort '(5 3 8 1 2) less)"));
+        assert_eq!("((1 a) (1 b) (2 c))",
+                   eval("(sort '((2 c) (1 a) (1 b)) (lambda (x y) (less (car x) (car y))))"));
+        assert_eq!("t", eval("(every less '(1 2) '(2 3))"));
+        assert_eq!("nil", eval("(every less '(1 5) '(2 3))"));
+        assert_eq!("t", eval("(some less '(5 1) '(2 3))"));
+        assert_eq!("nil", eval("(some less nil)"));
+    }
+
+    #[test]
+    fn test_eval_higher_order_errors() {
+        let mut instance = Scib::new();
+        let mut kind = |s: &str| instance.eval(s).unwrap_err().kind;
+        assert_eq!(ErrorKind::Type, kind("(funcall 1 2)"));
+        assert_eq!(ErrorKind::Type, kind("(funcall when 1)"));
+        assert_eq!(ErrorKind::Type, kind("(apply + 1)"));
+        assert_eq!(ErrorKind::Type, kind("(map car 1)"));
+        assert_eq!(ErrorKind::Arity, kind("(map (lambda () 1) '(1))"));
+        assert_eq!(ErrorKind::UnboundVariable("y".to_string()), kind("(sort '(1 2) (lambda (a b) y))"));
+    }
+
+    #[test]
+    fn test_eval_comparisons() {
+        let mut instance = Scib::new();
+        let mut eval = |s: &str| instance.eval(s).unwrap().to_string();
+        assert_eq!("t", eval("(= 1 1.0 2/2)"));
+        assert_eq!("(nil t nil)", eval("(list (= 9007199254740993 9007199254740992.0) (> 9007199254740993 9007199254740992.0) (< 9007199254740993 9007199254740992.0))"));
+        assert_eq!("nil", eval("(= 1 1 2)"));
+        assert_eq!("t", eval("(< 1 3/2 2 2.5)"));
+        assert_eq!("nil", eval("(< 1 3 2)"));
+        assert_eq!("t", eval("(> 3 2 1)"));
+        assert_eq!("t", eval("(<= 1 1 2)"));
+        assert_eq!("nil", eval("(>= 1 2)"));
+        assert_eq!("t", eval("(< 5)"));
+        assert_eq!("t", eval("(/= 1 2 3)"));
+        assert_eq!("nil", eval("(/= 1 2 1.0)"));
+        assert_eq!("nil", eval("(< 1 +nan.0)"));
+        assert_eq!("t", eval("(not nil)"));
+        assert_eq!("nil", eval("(not 0)"));
+        assert_eq!(ErrorKind::Type, instance.eval("(= 1 \"1\")").unwrap_err().kind);
+        assert_eq!(ErrorKind::Type, instance.eval("(< 2 1 'a)").unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_eval_and_or() {
+        let mut instance = Scib::new();
+        let mut eval = |s: &str| instance.eval(s).unwrap().to_string();
+        assert_eq!("t", eval("(and)"));
+        assert_eq!("nil", eval("(or)"));
+        assert_eq!("3", eval("(and 1 2 3)"));
+        assert_eq!("(1 2)", eval("(or nil '(1 2) 3)"));
+        // Evaluation stops at the first deciding argument.
+        assert_eq!("nil", eval("(and nil (error \"unreachable\"))"));
+        assert_eq!("1", eval("(or 1 (error \"unreachable\"))"));
+        assert_eq!("2", eval("(define x 0) (or (setq x 1) (setq x 2)) (and x (setq x 2)) x"));
+    }
+
+    #[test]
+    fn test_eval_equality() {
+        let mut instance = Scib::new();
+        let mut eval = |s: &str| instance.eval(s).unwrap().to_string();
+        assert_eq!("t", eval("(eq 'a 'a)"));
+        assert_eq!("t", eval("(eq 1 1)"));
+        assert_eq!("nil", eval("(eq \"a\" \"a\")"));
+        assert_eq!("t", eval("(let ((s \"a\")) (eq s s))"));
+        assert_eq!("nil", eval("(eq 1.5 1.5)"));
+        assert_eq!("t", eval("(eqv 1.5 1.5)"));
+        assert_eq!("t", eval("(eqv 1/2 2/4)"));
+        assert_eq!("nil", eval("(eqv 1 1.0)"));
+        assert_eq!("nil", eval("(eqv '(1) '(1))"));
+        assert_eq!("t", eval("(equal '(1 (2 \"x\")) (list 1 (list 2 \"x\")))"));
+        assert_eq!("t", eval("(equal (cons 1 (cons 2 nil)) '(1 2))"));
+        assert_eq!("t", eval("(equal '(1 2 . 3) (cons 1 (cons 2 3)))"));
+        assert_eq!("t", eval("(equal () nil)"));
+        assert_eq!("nil", eval("(equal '(1 2) '(1 2 3))"));
+        assert_eq!("nil", eval("(equal 1 1.0)"));
+        assert_eq!("((1) 2)", eval("(member (cons 1 nil) '(0 (1) 2))"));
+    }
+
+    #[test]
+    fn test_eval_numeric_library() {
+        let mut instance = Scib::new();
+        let mut eval = |s: &str| instance.eval(s).unwrap().to_string();
+        assert_eq!("(3 -3 1 -1 1 -1)", eval("(list (quotient 7 2) (quotient -7 2) (rem 7 2) (rem -7 2) (mod -7 2) (mod 7 -2))"));
+        assert_eq!("(5 1/2 2.5)", eval("(list (abs -5) (abs -1/2) (abs -2.5))"));
+        assert_eq!("(1 3 3.0)", eval("(list (min 3 1 2) (max 1 3 2) (max 1 3 2.0))"));
+        assert_eq!("(+nan.0 +nan.0 +nan.0)", eval("(list (min +nan.0 1) (min 1 +nan.0) (max +nan.0 1 2))"));
+        assert_eq!("(3 4 4 3)", eval("(list (floor 7/2) (ceiling 7/2) (round 7/2) (truncate 7/2))"));
+        assert_eq!("(-4.0 -3.0 -4.0 -3.0)", eval("(list (floor -3.5) (ceiling -3.5) (round -3.5) (truncate -3.5))"));
+        assert_eq!("(1024 1/8 1267650600228229401496703205376)", eval("(list (expt 2 10) (expt 2 -3) (expt 2 100))"));
+        assert_eq!("(4 3/5 1.5)", eval("(list (sqrt 16) (sqrt 9/25) (sqrt 2.25))"));
+        assert_eq!("(1.0 0.0 3.0)", eval("(list (exp 0) (log 1) (log 8 2))"));
+        assert_eq!("(0.0 1.0 0.0)", eval("(list (sin 0) (cos 0) (tan 0))"));
+        assert_eq!("(0.0 0.0 0.0)", eval("(list (asin 0) (acos 1) (atan 0))"));
+        assert_eq!("t", eval("(= (atan 1 -1) (* 3 (atan 1)))"));
+        assert_eq!("(\"255\" \"ff\" \"-101\" \"1/2\" \"2.0\")",
+                   eval("(list (number->string 255) (number->string 255 16) (number->string -5 2) (number->string 1/2) (number->string 2.0))"));
+        assert_eq!("(255 255 1/2 -2.5 nil nil)",
+                   eval("(list (string->number \"255\") (string->number \"ff\" 16) (string->number \"1/2\") (string->number \"-2.5\") (string->number \"abc\") (string->number \"1 2\"))"));
+        assert_eq!("(t nil t nil t t)", eval("(list (zero? 0) (zero? 1/2) (zero? 0.0) (even? 3) (even? -4) (odd? 99999999999999999999))"));
+    }
+
+    #[test]
+    fn test_eval_numeric_errors() {
+        let mut instance = Scib::new();
+        let mut kind = |s: &str| instance.eval(s).unwrap_err().kind;
+        assert_eq!(ErrorKind::DivisionByZero, kind("(/ 1 0)"));
+        assert_eq!(ErrorKind::DivisionByZero, kind("(/ 1.5 0.0)"));
+        assert_eq!(ErrorKind::DivisionByZero, kind("(/ 1 2 0)"));
+        assert_eq!(ErrorKind::DivisionByZero, kind("(quotient 1 0)"));
+        assert_eq!(ErrorKind::DivisionByZero, kind("(rem 1 0)"));
+        assert_eq!(ErrorKind::DivisionByZero, kind("(mod 1 0)"));
+        assert_eq!(ErrorKind::DivisionByZero, kind("(expt 0 -1)"));
+        assert_eq!(ErrorKind::Range, kind("(expt 2 9223372036854775807)"));
+        assert_eq!(ErrorKind::Type, kind("(mod 1/2 2)"));
+        assert_eq!(ErrorKind::Type, kind("(even? 1.5)"));
+        assert_eq!(ErrorKind::Type, kind("(sqrt \"4\")"));
+        assert_eq!(ErrorKind::Type, kind("(string->number 4)"));
+        assert_eq!(ErrorKind::Range, kind("(number->string 2.5 2)"));
+        assert_eq!(ErrorKind::Range, kind("(string->number \"11\" 3)"));
+        assert_eq!(ErrorKind::Arity, kind("(min)"));
+    }
 }
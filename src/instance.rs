@@ -1,167 +1,237 @@
 use types::*;
 use builtins::*;
-use parse::parse;
-use lex::lex;
-use eval::eval;
+use eval;
+use reader::Reader;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, Read, Result, Error, ErrorKind};
+use error::{Result, Error, ErrorKind, Location};
+use std::io::Read;
+use source_map::SourceMap;
+use std::mem;
 use std::rc::Rc;
 
 pub struct Scib {
     definitions: HashMap<String, Rc<Value>>,
+    env: Option<Rc<Environment>>,
+    source_map: SourceMap,
 }
 
 impl Scib {
     pub fn new() -> Self {
         let mut instance = Scib {
             definitions: HashMap::new(),
+            env: None,
+            source_map: SourceMap::new(),
         };
-        instance.set(String::from("setq"),
-                     Rc::new(Value::Macro(Rc::new(
-                         Macro {
-                             params: Parameters {
-                                 required: vec![String::from("_setq-label"),
-                                                String::from("_setq-value")],
-                                 optional: vec![],
-                                 rest: None,
-                             },
-                             body: Body::Rust(setq_f),
-                         }))));
-        instance.set(String::from("="),
-                     Rc::new(Value::Function(Rc::new(
-                         Function {
-                             params: Parameters {
-                                 required: vec![String::from("_=-first")],
-                                 optional: vec![],
-                                 rest: Some(String::from("_=-rest")),
-                             },
-                             body: Body::Rust(equalsign_f),
-                         }))));
-        instance.set(String::from("+"),
-                     Rc::new(Value::Function(Rc::new(
-                         Function {
-                             params: Parameters {
-                                 required: vec![],
-                                 optional: vec![],
-                                 rest: Some(String::from("_+")),
-                             },
-                             body: Body::Rust(sum_f),
-                         }))));
-        instance.set(String::from("-"),
-                     Rc::new(Value::Function(Rc::new(
-                         Function {
-                             params: Parameters {
-                                 required: vec![String::from("_--positive")],
-                                 optional: vec![],
-                                 rest: Some(String::from("_--negatives")),
-                             },
-                             body: Body::Rust(difference_f),
-                         }))));
-        instance.set(String::from("*"),
-                     Rc::new(Value::Function(Rc::new(
-                         Function {
-                             params: Parameters {
-                                 required: vec![],
-                                 optional: vec![],
-                                 rest: Some(String::from("_*")),
-                             },
-                             body: Body::Rust(product_f),
-                         }))));
-        instance.set(String::from("/"),
-                     Rc::new(Value::Function(Rc::new(
-                         Function {
-                             params: Parameters {
-                                 required: vec![String::from("_/-numerator")],
-                                 optional: vec![],
-                                 rest: Some(String::from("_/-denominator")),
-                             },
-                             body: Body::Rust(quotient_f),
-                         }))));
-        instance.set(String::from("list"),
-                     Rc::new(Value::Function(Rc::new(
-                         Function {
-                             params: Parameters {
-                                 required: vec![],
-                                 optional: vec![],
-                                 rest: Some(String::from("_list-rest")),
-                             },
-                             body: Body::Rust(list_f),
-                         }))));
-        instance.set(String::from("progn"),
-                     Rc::new(Value::Macro(Rc::new(
-                         Macro {
-                             params: Parameters {
-                                 required: vec![],
-                                 optional: vec![],
-                                 rest: Some(String::from("_progn-rest")),
-                             },
-                             body: Body::Rust(progn_f),
-                         }))));
-        instance.set(String::from("if"),
-                     Rc::new(Value::Macro(Rc::new(
-                         Macro {
-                             params: Parameters {
-                                 required: vec![String::from("_if-cond"), String::from("_if-iftrue")],
-                                 optional: vec![],
-                                 rest: Some(String::from("_if-iffalse")),
-                             },
-                             body: Body::Rust(if_f),
-                         }))));
-        instance.set(String::from("defmacro"),
-                     Rc::new(Value::Macro(Rc::new(
-                         Macro {
-                             params: Parameters {
-                                 required: vec![String::from("_defmacro-name")],
-                                 optional: vec![],
-                                 rest: Some(String::from("_defmacro-value")),
-                             },
-                             body: Body::Rust(defmacro_f),
-                         }))));
-        instance.set(String::from("define"),
-                     Rc::new(Value::Macro(Rc::new(
-                         Macro {
-                             params: Parameters {
-                                 required: vec![String::from("_define-name")],
-                                 optional: vec![],
-                                 rest: Some(String::from("_define-value")),
-                             },
-                             body: Body::Rust(define_f),
-                         }))));
+        instance.register_fn("error", Parameters::new(&["value"], &[], None), error_f);
+        instance.register_macro("setq", Parameters::new(&["label", "value"], &[], None), setq_f);
+        instance.register_fn("=", Parameters::new(&["first"], &[], Some("rest")), equalsign_f);
+        instance.register_fn("/=", Parameters::new(&["first"], &[], Some("rest")), not_equal_f);
+        instance.register_fn("<", Parameters::new(&["first"], &[], Some("rest")), less_f);
+        instance.register_fn(">", Parameters::new(&["first"], &[], Some("rest")), greater_f);
+        instance.register_fn("<=", Parameters::new(&["first"], &[], Some("rest")), less_equal_f);
+        instance.register_fn(">=", Parameters::new(&["first"], &[], Some("rest")), greater_equal_f);
+        instance.register_fn("not", Parameters::new(&["value"], &[], None), not_f);
+        instance.register_macro("and", Parameters::new(&[], &[], Some("forms")), and_f);
+        instance.register_macro("or", Parameters::new(&[], &[], Some("forms")), or_f);
+        instance.register_fn("eq", Parameters::new(&["a", "b"], &[], None), eq_f);
+        instance.register_fn("eqv", Parameters::new(&["a", "b"], &[], None), eqv_f);
+        instance.register_fn("equal", Parameters::new(&["a", "b"], &[], None), equal_f);
+        instance.register_fn("+", Parameters::new(&[], &[], Some("numbers")), sum_f);
+        instance.register_fn("-", Parameters::new(&["number"], &[], Some("numbers")), difference_f);
+        instance.register_fn("*", Parameters::new(&[], &[], Some("numbers")), product_f);
+        instance.register_fn("/", Parameters::new(&["number"], &[], Some("numbers")), divide_f);
+        instance.register_fn("quotient", Parameters::new(&["dividend", "divisor"], &[], None), quotient_f);
+        instance.register_fn("rem", Parameters::new(&["dividend", "divisor"], &[], None), rem_f);
+        instance.register_fn("mod", Parameters::new(&["dividend", "divisor"], &[], None), mod_f);
+        instance.register_fn("abs", Parameters::new(&["number"], &[], None), abs_f);
+        instance.register_fn("min", Parameters::new(&["number"], &[], Some("numbers")), min_f);
+        instance.register_fn("max", Parameters::new(&["number"], &[], Some("numbers")), max_f);
+        instance.register_fn("floor", Parameters::new(&["number"], &[], None), floor_f);
+        instance.register_fn("ceiling", Parameters::new(&["number"], &[], None), ceiling_f);
+        instance.register_fn("round", Parameters::new(&["number"], &[], None), round_f);
+        instance.register_fn("truncate", Parameters::new(&["number"], &[], None), truncate_f);
+        instance.register_fn("expt", Parameters::new(&["base", "power"], &[], None), expt_f);
+        instance.register_fn("sqrt", Parameters::new(&["number"], &[], None), sqrt_f);
+        instance.register_fn("exp", Parameters::new(&["number"], &[], None), exp_f);
+        instance.register_fn("log", Parameters::new(&["number"], &["base"], None), log_f);
+        instance.register_fn("sin", Parameters::new(&["number"], &[], None), sin_f);
+        instance.register_fn("cos", Parameters::new(&["number"], &[], None), cos_f);
+        instance.register_fn("tan", Parameters::new(&["number"], &[], None), tan_f);
+        instance.register_fn("asin", Parameters::new(&["number"], &[], None), asin_f);
+        instance.register_fn("acos", Parameters::new(&["number"], &[], None), acos_f);
+        instance.register_fn("atan", Parameters::new(&["y"], &["x"], None), atan_f);
+        instance.register_fn("number->string", Parameters::new(&["number"], &["radix"], None), number_to_string_f);
+        instance.register_fn("string->number", Parameters::new(&["string"], &["radix"], None), string_to_number_f);
+        instance.register_fn("zero?", Parameters::new(&["number"], &[], None), zero_f);
+        instance.register_fn("even?", Parameters::new(&["number"], &[], None), even_f);
+        instance.register_fn("odd?", Parameters::new(&["number"], &[], None), odd_f);
+        instance.register_fn("char->integer", Parameters::new(&["char"], &[], None), char_to_integer_f);
+        instance.register_fn("integer->char", Parameters::new(&["integer"], &[], None), integer_to_char_f);
+        instance.register_fn("string-ref", Parameters::new(&["string", "index"], &[], None), string_ref_f);
+        instance.register_fn("list", Parameters::new(&[], &[], Some("values")), list_f);
+        instance.register_fn("cons", Parameters::new(&["car", "cdr"], &[], None), cons_f);
+        instance.register_fn("car", Parameters::new(&["list"], &[], None), car_f);
+        instance.register_fn("cdr", Parameters::new(&["list"], &[], None), cdr_f);
+        instance.register_fn("set-car!", Parameters::new(&["pair", "value"], &[], None), set_car_f);
+        instance.register_fn("set-cdr!", Parameters::new(&["pair", "value"], &[], None), set_cdr_f);
+        instance.register_fn("length", Parameters::new(&["list"], &[], None), length_f);
+        instance.register_fn("nth", Parameters::new(&["n", "list"], &[], None), nth_f);
+        instance.register_fn("list-ref", Parameters::new(&["list", "index"], &[], None), list_ref_f);
+        instance.register_fn("append", Parameters::new(&[], &[], Some("lists")), append_f);
+        instance.register_fn("reverse", Parameters::new(&["list"], &[], None), reverse_f);
+        instance.register_fn("last", Parameters::new(&["list"], &[], None), last_f);
+        instance.register_fn("member", Parameters::new(&["item", "list"], &[], None), member_f);
+        instance.register_fn("assoc", Parameters::new(&["key", "alist"], &[], None), assoc_f);
+        instance.register_fn("null?", Parameters::new(&["value"], &[], None), null_f);
+        instance.register_fn("funcall", Parameters::new(&["function"], &[], Some("args")), funcall_f);
+        instance.register_fn("apply", Parameters::new(&["function"], &[], Some("args")), apply_f);
+        instance.register_fn("map", Parameters::new(&["function", "list"], &[], Some("lists")), map_f);
+        instance.register_fn("filter", Parameters::new(&["predicate", "list"], &[], None), filter_f);
+        instance.register_fn("reduce", Parameters::new(&["function", "list"], &["initial"], None), reduce_f);
+        instance.register_fn("sort", Parameters::new(&["list", "less"], &[], None), sort_f);
+        instance.register_fn("every", Parameters::new(&["predicate", "list"], &[], Some("lists")), every_f);
+        instance.register_fn("some", Parameters::new(&["predicate", "list"], &[], Some("lists")), some_f);
+        instance.register_macro("progn", Parameters::new(&[], &[], Some("body")), progn_f);
+        instance.register_macro("if", Parameters::new(&["cond", "then"], &[], Some("else")), if_f);
+        instance.register_macro("let", Parameters::new(&["bindings"], &[], Some("body")), let_f);
+        instance.register_macro("lambda", Parameters::new(&["params"], &[], Some("body")), lambda_f);
+        instance.register_macro("defmacro", Parameters::new(&["signature"], &[], Some("body")), defmacro_f);
+        instance.register_macro("define", Parameters::new(&["name"], &[], Some("body")), define_f);
         instance.eval("(defmacro (when cond &rest rest) `(if ,cond (progn ,@rest)))").unwrap();
         instance
     }
 
     pub fn eval_file(&mut self, file_name: &str) -> Result<Rc<Value>> {
-        let file = try!(File::open(file_name));
-        let chars = BufReader::new(file).bytes().map(|r| r.unwrap() as char).fuse();
-        let exprs = try!(parse(try!(lex(chars))));
+        let file = File::open(file_name)?;
+        let file_name: Rc<str> = Rc::from(file_name);
+        self.eval_all(&mut Reader::from_read(file, Some(file_name)))
+    }
+
+    /// Evaluates the UTF-8 source text read from `reader`.  Each datum is
+    /// evaluated as soon as it has been read.
+    pub fn eval_reader<R: Read>(&mut self, reader: R) -> Result<Rc<Value>> {
+        self.eval_all(&mut Reader::from_read(reader, None))
+    }
+
+    pub fn eval(&mut self, string: &str) -> Result<Rc<Value>> {
+        self.eval_all(&mut Reader::new(string.chars(), None))
+    }
+
+    /// Reads and evaluates each datum from `reader` in turn, returning the
+    /// value of the last one.  Each datum is evaluated before the next is
+    /// read, so a syntax error only stops evaluation where it occurs.
+    pub fn eval_all<I: Iterator<Item = char>>(&mut self, reader: &mut Reader<I>) -> Result<Rc<Value>> {
         let mut result = Rc::new(Value::Nil);
-        for expr in exprs {
-            result = try!(eval(self, &expr));
+        loop {
+            self.source_map.prune();
+            match reader.read_into(&mut self.source_map)? {
+                Some(expr) => result = progn_value(self, &[expr])?,
+                None => return Ok(result),
+            }
         }
-        Ok(result)
     }
 
-    pub fn eval(&mut self, string: &str) -> Result<Rc<Value>> {
-        let exprs = try!(parse(try!(lex(string.chars().fuse()))));
-        progn(self, &exprs)
+    /// Calls the Lisp or Rust function `function` with `args`, which are
+    /// passed as they are rather than evaluated.
+    pub fn call(&mut self, function: &Rc<Value>, args: &[Rc<Value>]) -> Result<Rc<Value>> {
+        eval::call(self, function, args)
     }
 
     pub fn lookup(&self, name: &str) -> Result<Rc<Value>> {
-        if let Some(v) = self.definitions.get(name) { Ok(v.clone()) }
-        else { Err(Error::new(ErrorKind::InvalidData, format!("Unbound label {}", name))) }
+        if let Some(v) = self.env.as_ref().and_then(|env| env.lookup(name)) { Ok(v) }
+        else if let Some(v) = self.definitions.get(name) { Ok(v.clone()) }
+        else { Err(Error::new(ErrorKind::UnboundVariable(String::from(name)), format!("Unbound label {}", name))) }
+    }
+
+    /// Binds `name` globally to a function implemented in Rust.  `f` may
+    /// capture host state.  It is passed its evaluated arguments, which
+    /// have already been checked against `params`.
+    pub fn register_fn<F>(&mut self, name: &str, params: Parameters, f: F) -> Option<Rc<Value>>
+        where F: Fn(&mut Scib, &[Rc<Value>]) -> Result<Rc<Value>> + 'static {
+        self.set(String::from(name),
+                 Rc::new(Value::Function(Rc::new(
+                     Function {
+                         params,
+                         body: Body::Rust(Box::new(f)),
+                         env: None,
+                     }))))
     }
 
+    /// Binds `name` globally to a macro implemented in Rust.  `f` sees its
+    /// arguments unevaluated and its result is evaluated in place of the
+    /// macro call.
+    pub fn register_macro<F>(&mut self, name: &str, params: Parameters, f: F) -> Option<Rc<Value>>
+        where F: Fn(&mut Scib, &[Rc<Value>]) -> Result<Rc<Value>> + 'static {
+        self.set(String::from(name),
+                 Rc::new(Value::Macro(Rc::new(
+                     Macro {
+                         params,
+                         body: Body::Rust(Box::new(f)),
+                         env: None,
+                     }))))
+    }
+
+    /// Gets the global binding of `name`, ignoring any local environment.
     pub fn get<'a>(&'a mut self, name: &str) -> Option<&'a Rc<Value>> {
         self.definitions.get(name)
     }
 
+    /// Sets the global binding of `name`, ignoring any local environment.
     pub fn set(&mut self, name: String, value: Rc<Value>) -> Option<Rc<Value>> {
         self.definitions.insert(name, value)
     }
 
+    /// Binds `name` in the innermost environment, or globally if there is
+    /// no local environment.  This is what `define` does, so a definition
+    /// in a function or `let` body is local to it.
+    pub fn define(&mut self, name: String, value: Rc<Value>) -> Option<Rc<Value>> {
+        match self.env {
+            Some(ref env) => env.define(name, value),
+            None => self.set(name, value),
+        }
+    }
+
+    /// Rebinds `name` in the innermost environment that binds it, or
+    /// globally if no local environment does.
+    pub fn assign(&mut self, name: String, value: Rc<Value>) -> Option<Rc<Value>> {
+        let value = match self.env {
+            Some(ref env) => match env.assign(&name, value) {
+                Ok(old) => return Some(old),
+                Err(value) => value,
+            },
+            None => value,
+        };
+        self.set(name, value)
+    }
+
+    /// Removes `name` from the innermost environment, or from the globals
+    /// if there is no local environment.
     pub fn unbind(&mut self, name: &str) -> Option<Rc<Value>> {
-        self.definitions.remove(name)
+        match self.env {
+            Some(ref env) => env.unbind(name),
+            None => self.definitions.remove(name),
+        }
+    }
+
+    /// Gets where `value` was read from, if it came from source text.
+    pub fn location(&self, value: &Rc<Value>) -> Option<Location> {
+        self.source_map.get(value).cloned()
+    }
+
+    pub fn environment(&self) -> Option<Rc<Environment>> {
+        self.env.clone()
+    }
+
+    /// Replaces the current local environment, returning the old one.
+    /// `None` means evaluation happens directly against the globals.
+    pub fn set_environment(&mut self, env: Option<Rc<Environment>>) -> Option<Rc<Environment>> {
+        mem::replace(&mut self.env, env)
+    }
+}
+
+impl Default for Scib {
+    fn default() -> Self {
+        Scib::new()
     }
 }
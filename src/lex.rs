@@ -1,6 +1,9 @@
 use types::Value;
-use std::io;
-use std::io::{Error, ErrorKind};
+use bignum::{BigInt, Rational};
+use numeric::Number;
+use std::iter::Fuse;
+use std::rc::Rc;
+use error::{Result, Error, ErrorKind, Location};
 
 #[derive(Debug, PartialEq)]
 pub enum Token {
@@ -11,227 +14,715 @@ pub enum Token {
     UnquoteList,
     OpenParen,
     CloseParen,
+    /// A lone `.`, which separates the last cdr in dotted pair syntax.
+    Dot,
+    /// `#;`, which comments out the datum following it.
+    DatumComment,
 }
 
-fn is_separator_char(c: char) -> bool { c == '(' || c == ')' || c.is_whitespace() }
+fn is_separator_char(c: char) -> bool { c == '(' || c == ')' || c == ';' || c.is_whitespace() }
 
 fn is_label_character(ch: char) -> bool {
-    !(ch.is_whitespace() || ch == '`' || ch == '\'' || ch == '(' || ch == ')' || ch == '"')
+    !(ch.is_whitespace() || ch == '`' || ch == '\'' || ch == '(' || ch == ')' || ch == '"' || ch == ';')
 }
 
-fn label_to_token(s: String) -> Token {
-    use std::str::FromStr;
-    Token::Value(
+/// The names accepted after `#\`, besides single characters and `x`
+/// followed by a hexadecimal code point.
+pub const CHAR_NAMES: &[(&str, char)] = &[
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("delete", '\u{7f}'),
+    ("escape", '\u{1b}'),
+    ("newline", '\n'),
+    ("null", '\0'),
+    ("return", '\r'),
+    ("space", ' '),
+    ("tab", '\t'),
+];
+
+/// Interprets the text of a character literal following `#\\`.
+fn name_to_char(name: &str, location: &Location) -> Result<char> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(c);
+    }
+    if let Some(&(_, c)) = CHAR_NAMES.iter().find(|&&(n, _)| n == name) {
+        return Ok(c);
+    }
+    if let Some(hex) = name.strip_prefix('x') {
+        if let Some(c) = u32::from_str_radix(hex, 16).ok().and_then(::std::char::from_u32) {
+            return Ok(c);
+        }
+    }
+    Err(Error::new(ErrorKind::Lex,
+                   format!("Invalid character literal '#\\{}'", name))
+        .at(location.clone()))
+}
+
+/// Removes `_` digit separators from `s`, or returns `None` if one isn't
+/// between two digits.
+fn strip_separators(s: &str, radix: u32) -> Option<String> {
+    let chars: Vec<char> = s.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '_' && !(i > 0 && chars[i - 1].is_digit(radix) &&
+                         chars.get(i + 1).is_some_and(|c| c.is_digit(radix))) {
+            return None;
+        }
+    }
+    Some(chars.into_iter().filter(|&c| c != '_').collect())
+}
+
+/// Parses a numeric literal: an integer, a rational such as `1/3`, or a
+/// float.  `#x`, `#o`, `#b` and `#d` prefixes select the radix of exact
+/// numbers and `_` may separate digits.
+pub fn parse_number(s: &str) -> Option<Value> {
+    match s {
+        "+inf.0" => return Some(Value::Number(f64::INFINITY)),
+        "-inf.0" => return Some(Value::Number(f64::NEG_INFINITY)),
+        "+nan.0" | "-nan.0" => return Some(Value::Number(f64::NAN)),
+        _ => {},
+    }
+    let (radix, s) = match s.get(..2) {
+        Some("#x") | Some("#X") => (16, &s[2..]),
+        Some("#o") | Some("#O") => (8, &s[2..]),
+        Some("#b") | Some("#B") => (2, &s[2..]),
+        Some("#d") | Some("#D") => (10, &s[2..]),
+        _ => (10, s),
+    };
+    let s = strip_separators(s, radix)?;
+    if let Ok(i) = i64::from_str_radix(&s, radix) {
+        Some(Value::Integer(i))
+    } else if let Ok(i) = BigInt::from_str_radix(&s, radix) {
+        Some(Value::BigInt(i))
+    } else if let Ok(r) = Rational::from_str_radix(&s, radix) {
+        Some(Value::from(Number::from_rational(r)))
+    } else if radix == 10 && s.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c)) {
+        // Checking the characters first keeps f64's spellings of infinity
+        // and NaN as labels.
+        s.parse().ok().map(Value::Number)
+    } else {
+        None
+    }
+}
+
+/// Whether `s` has a radix prefix followed by something that starts like a
+/// number, so failing to parse it is an error rather than a label such as
+/// `#define`.
+fn looks_like_radix_number(s: &str) -> bool {
+    let radix = match s.get(..2) {
+        Some("#x") | Some("#X") => 16,
+        Some("#o") | Some("#O") => 8,
+        Some("#b") | Some("#B") => 2,
+        Some("#d") | Some("#D") => 10,
+        _ => return false,
+    };
+    s[2..].chars().next().is_some_and(|c| c.is_digit(radix) || "+-.".contains(c))
+}
+
+fn label_to_token(s: String, location: &Location) -> Result<Token> {
+    if s == "." {
+        return Ok(Token::Dot);
+    }
+    Ok(Token::Value(
         if s == "t" {
             Value::True
         } else if s == "nil" {
             Value::Nil
-        } else if let Ok(n) = f64::from_str(s.as_str()) {
-            Value::Number(n)
+        } else if let Some(n) = parse_number(&s) {
+            n
+        } else if looks_like_radix_number(&s) {
+            return Err(Error::new(ErrorKind::Lex,
+                                  format!("Invalid number '{}'", s))
+                       .at(location.clone()));
         } else {
             Value::Label(s)
-        })
+        }))
 }
 
-pub fn lex<I: Iterator<Item = char>>(mut iter: I) -> io::Result<Vec<Token>> {
-    let mut vec = Vec::new();
-    let mut ch = match iter.next() {
-        Some(ch) => ch,
-        None => return Ok(vec),
-    };
-    'outer: loop {
-        if ch == '(' {
-            vec.push(Token::OpenParen);
-        } else if ch == ')' {
-            vec.push(Token::CloseParen);
-        } else if ch == '"' {
-            let mut s = String::new();
-            while let Some(cn) = iter.next() {
-                if cn == '\\' {
-                    s.push(match iter.next() {
-                        Some('\\') => '\\',
-                        Some('"') => '"',
-                        Some('t') => '\t',
-                        Some('n') => '\n',
-                        Some(x) => return Err(Error::new(ErrorKind::InvalidInput,
-                                                         format!("Invalid escape sequence `\\{}'.", x))),
-                        None => return Err(Error::new(ErrorKind::InvalidInput,
-                                                      format!("Nothing to escape and the string isn't closed."))),
-                    });
-                } else if cn == '"' {
-                    vec.push(Token::Value(Value::String(s)));
-                    match iter.next() {
-                        Some(c) => {
-                            ch = c;
-                            continue 'outer;
-                        },
-                        None => break 'outer,
-                    }
-                } else {
-                    s.push(cn);
-                }
+/// Wraps a `char` iterator, tracking the position of the last character
+/// returned.
+struct Positioned<I> {
+    iter: I,
+    file: Option<Rc<str>>,
+    line: usize,
+    column: usize,
+    after_newline: bool,
+}
+
+impl<I: Iterator<Item = char>> Positioned<I> {
+    fn new(iter: I, file: Option<Rc<str>>) -> Self {
+        Positioned { iter, file, line: 1, column: 0, after_newline: false }
+    }
+
+    fn location(&self) -> Location {
+        Location { file: self.file.clone(), line: self.line, column: self.column }
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for Positioned<I> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.iter.next();
+        if let Some(c) = c {
+            if self.after_newline {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
             }
-            return Err(Error::new(ErrorKind::InvalidInput,
-                                  format!("String not ended")));
-        } else if ch == '\'' {
-            vec.push(Token::Quote);
-        } else if ch == '`' {
-            vec.push(Token::Backquote);
-        } else if ch == ',' {
+            self.after_newline = c == '\n';
+        }
+        c
+    }
+}
+
+/// Reads the rest of a label starting with `s`, where `c` is the character
+/// following `s`.  Returns the label and the first character after it.
+fn lex_label<I: Iterator<Item = char>>(mut s: String, mut c: Option<char>, iter: &mut Positioned<I>) -> Result<(String, Option<char>)> {
+    while let Some(cn) = c {
+        if is_label_character(cn) {
+            s.push(cn);
+        } else if is_separator_char(cn) {
+            break;
+        } else {
+            return Err(Error::new(ErrorKind::Lex,
+                                  format!("Invalid character encountered after a number or label '{}'.  Consider adding a space.", cn))
+                       .at(iter.location()));
+        }
+        c = iter.next();
+    }
+    Ok((s, c))
+}
+
+enum Escape {
+    Char(char),
+    /// A backslash ending a line, which removes the line break and the
+    /// indentation after it.  Holds the character after the indentation.
+    Continuation(Option<char>),
+}
+
+/// Reads hexadecimal digits up to `end` as a code point.
+fn lex_hex_escape<I: Iterator<Item = char>>(iter: &mut Positioned<I>, end: char, location: &Location) -> Result<char> {
+    let mut digits = String::new();
+    loop {
+        match iter.next() {
+            Some(c) if c == end => break,
+            Some(c) if c.is_ascii_hexdigit() && digits.len() < 6 => digits.push(c),
+            Some(c) => return Err(Error::new(ErrorKind::Lex,
+                                             format!("Invalid character `{}' in a hexadecimal escape; expected a digit or `{}'.", c, end))
+                                  .at(iter.location())),
+            None => return Err(Error::new(ErrorKind::Incomplete,
+                                          "String not ended")
+                               .at(location.clone())),
+        }
+    }
+    u32::from_str_radix(&digits, 16).ok().and_then(::std::char::from_u32).ok_or_else(|| {
+        Error::new(ErrorKind::Lex,
+                   format!("Escape `{}' is not a Unicode scalar value.", digits))
+            .at(iter.location())
+    })
+}
+
+/// Interprets the escape sequence after a backslash in a string, where `c`
+/// is the character following the backslash.
+fn lex_escape<I: Iterator<Item = char>>(c: Option<char>, iter: &mut Positioned<I>, location: &Location) -> Result<Escape> {
+    Ok(Escape::Char(match c {
+        Some('\\') => '\\',
+        Some('"') => '"',
+        Some('t') => '\t',
+        Some('n') => '\n',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some('x') => lex_hex_escape(iter, ';', location)?,
+        Some('u') => {
             match iter.next() {
-                Some('@') => vec.push(Token::UnquoteList),
-                Some(c) => {
-                    vec.push(Token::Unquote);
-                    ch = c;
-                    continue;
-                },
-                None => break,
+                Some('{') => lex_hex_escape(iter, '}', location)?,
+                Some(_) => return Err(Error::new(ErrorKind::Lex,
+                                                 "Expected `{' after `\\u'.")
+                                      .at(iter.location())),
+                None => return Err(Error::new(ErrorKind::Incomplete,
+                                              "String not ended")
+                                   .at(location.clone())),
+            }
+        },
+        Some(' ') | Some('\t') | Some('\r') | Some('\n') => {
+            let mut c = c;
+            while c == Some(' ') || c == Some('\t') || c == Some('\r') {
+                c = iter.next();
+            }
+            if c.is_none() {
+                return Err(Error::new(ErrorKind::Incomplete,
+                                      "String not ended")
+                           .at(location.clone()));
+            }
+            if c != Some('\n') {
+                return Err(Error::new(ErrorKind::Lex,
+                                      "Only whitespace may follow a line continuation backslash.")
+                           .at(iter.location()));
             }
-        } else if ch.is_whitespace() {
-        } else if is_label_character(ch) {
-            let mut s = String::new();
-            s.push(ch);
             let mut c = iter.next();
-            while c.is_some() {
-                let cn = c.unwrap();
-                if is_label_character(cn) {
-                    s.push(cn);
-                } else if is_separator_char(cn) {
-                    vec.push(label_to_token(s));
-                    ch = cn;
-                    continue 'outer;
-                } else {
-                    return Err(Error::new(ErrorKind::InvalidInput,
-                                          format!("Invalid character encountered after a number or label '{}'.  Consider adding a space.", cn)));
-                }
+            while c == Some(' ') || c == Some('\t') {
                 c = iter.next();
             }
-            // end of file
-            vec.push(label_to_token(s));
-            break;
-        } else {
-            unreachable!();
+            return Ok(Escape::Continuation(c));
+        },
+        Some(x) => return Err(Error::new(ErrorKind::Lex,
+                                         format!("Invalid escape sequence `\\{}'.", x))
+                              .at(iter.location())),
+        None => return Err(Error::new(ErrorKind::Incomplete,
+                                      "Nothing to escape and the string isn't closed.")
+                           .at(location.clone())),
+    }))
+}
+
+/// Skips a `#| ... |#` comment, including any comments nested in it.  The
+/// opening `#|` has already been read.
+fn skip_block_comment<I: Iterator<Item = char>>(iter: &mut Positioned<I>, location: &Location) -> Result<()> {
+    let mut depth = 1;
+    let mut prev = None;
+    for c in iter.by_ref() {
+        match (prev, c) {
+            (Some('|'), '#') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+                prev = None;
+            },
+            (Some('#'), '|') => {
+                depth += 1;
+                prev = None;
+            },
+            _ => prev = Some(c),
         }
-        match iter.next() {
-            Some(c) => ch = c,
-            None => break,
+    }
+    Err(Error::new(ErrorKind::Incomplete,
+                   "Block comment not ended")
+        .at(location.clone()))
+}
+
+/// Splits a stream of characters into tokens, each paired with the
+/// location of its first character.  Tokens are produced as they are
+/// needed, so lexing can start before the input ends.
+pub struct Lexer<I: Iterator<Item = char>> {
+    iter: Positioned<Fuse<I>>,
+    /// The character following the last token, if reading the token
+    /// required looking at it.
+    pending: Option<char>,
+}
+
+impl<I: Iterator<Item = char>> Lexer<I> {
+    /// `file` names the source in the locations of tokens.
+    pub fn new(iter: I, file: Option<Rc<str>>) -> Self {
+        Lexer { iter: Positioned::new(iter.fuse(), file), pending: None }
+    }
+
+    fn lex_string(&mut self, location: &Location) -> Result<Token> {
+        let mut s = String::new();
+        let mut next = self.iter.next();
+        while let Some(cn) = next {
+            next = self.iter.next();
+            if cn == '\\' {
+                match lex_escape(next, &mut self.iter, location)? {
+                    Escape::Char(c) => {
+                        s.push(c);
+                        next = self.iter.next();
+                    },
+                    Escape::Continuation(after) => next = after,
+                }
+            } else if cn == '"' {
+                self.pending = next;
+                return Ok(Token::Value(Value::String(s)));
+            } else {
+                s.push(cn);
+            }
         }
+        Err(Error::new(ErrorKind::Incomplete,
+                       "String not ended")
+            .at(location.clone()))
+    }
+
+    fn next_token(&mut self) -> Result<Option<(Token, Location)>> {
+        loop {
+            let ch = match self.pending.take().or_else(|| self.iter.next()) {
+                Some(ch) => ch,
+                None => return Ok(None),
+            };
+            let location = self.iter.location();
+            let token = if ch == '(' {
+                Token::OpenParen
+            } else if ch == ')' {
+                Token::CloseParen
+            } else if ch == '"' {
+                self.lex_string(&location)?
+            } else if ch == '\'' {
+                Token::Quote
+            } else if ch == '`' {
+                Token::Backquote
+            } else if ch == ',' {
+                match self.iter.next() {
+                    Some('@') => Token::UnquoteList,
+                    c => {
+                        self.pending = c;
+                        Token::Unquote
+                    },
+                }
+            } else if ch == ';' {
+                for c in self.iter.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                continue;
+            } else if ch.is_whitespace() {
+                continue;
+            } else if is_label_character(ch) {
+                let c = self.iter.next();
+                if ch == '#' && c == Some('|') {
+                    skip_block_comment(&mut self.iter, &location)?;
+                    continue;
+                } else if ch == '#' && c == Some(';') {
+                    Token::DatumComment
+                } else if ch == '#' && c == Some('\\') {
+                    // The first character is taken as is, so `#\(` and `#\ `
+                    // work.
+                    let first = match self.iter.next() {
+                        Some(first) => first,
+                        None => return Err(Error::new(ErrorKind::Incomplete,
+                                                      "Character literal not ended")
+                                           .at(location)),
+                    };
+                    let c = self.iter.next();
+                    let (name, c) = lex_label(first.to_string(), c, &mut self.iter)?;
+                    self.pending = c;
+                    Token::Value(Value::Char(name_to_char(&name, &location)?))
+                } else {
+                    let (s, c) = lex_label(ch.to_string(), c, &mut self.iter)?;
+                    self.pending = c;
+                    label_to_token(s, &location)?
+                }
+            } else {
+                unreachable!();
+            };
+            return Ok(Some((token, location)));
+        }
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for Lexer<I> {
+    type Item = Result<(Token, Location)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token().transpose()
     }
-    Ok(vec)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn lex<I: Iterator<Item = char>>(iter: I, file: Option<Rc<str>>) -> Result<Vec<(Token, Location)>> {
+        Lexer::new(iter, file).collect()
+    }
+
+    fn tokens(s: &str) -> Vec<Token> {
+        lex(s.chars().fuse(), None).unwrap().into_iter().map(|(t, _)| t).collect()
+    }
+
     #[test]
     fn test_lex_1() {
         assert_eq!(
-            vec![Token::OpenParen, Token::Value(Value::Label("setq".to_string())), Token::Value(Value::Number(13.0)), Token::CloseParen],
-            lex("(setq 13)".chars().fuse()).unwrap());
+            vec![Token::OpenParen, Token::Value(Value::Label("setq".to_string())), Token::Value(Value::Integer(13)), Token::CloseParen],
+            tokens("(setq 13)"));
     }
 
     #[test]
     fn test_lex_2() {
         assert_eq!(
             vec![Token::OpenParen, Token::Value(Value::Label("setq".to_string())), Token::Value(Value::Number(0.13)), Token::CloseParen],
-            lex("(setq .13)".chars().fuse()).unwrap());
+            tokens("(setq .13)"));
     }
 
     #[test]
     fn test_lex_3() {
         assert_eq!(
             vec![Token::OpenParen, Token::Value(Value::Label("setq".to_string())), Token::Value(Value::Number(13.123)), Token::CloseParen],
-            lex("(setq 13.123)".chars().fuse()).unwrap());
+            tokens("(setq 13.123)"));
     }
 
     #[test]
     fn test_lex_4() {
         assert_eq!(
             vec![Token::OpenParen, Token::Value(Value::Label("setq".to_string())), Token::Value(Value::Number(13.0)), Token::CloseParen],
-            lex("(setq 13.)".chars().fuse()).unwrap());
+            tokens("(setq 13.)"));
     }
 
     #[test]
     fn test_lex_5() {
         assert_eq!(
             vec![Token::OpenParen, Token::Value(Value::Label("setq".to_string())), Token::Value(Value::Number(13.0)), Token::Value(Value::Label("abcd".to_string())), Token::CloseParen],
-            lex("(setq 13. abcd)".chars().fuse()).unwrap());
+            tokens("(setq 13. abcd)"));
     }
 
     #[test]
     fn test_lex_6() {
         assert_eq!(
-            vec![Token::Value(Value::Number(13.0))],
-            lex("13".chars().fuse()).unwrap());
+            vec![Token::Value(Value::Integer(13))],
+            tokens("13"));
     }
 
     #[test]
     fn test_lex_7() {
         assert_eq!(
             vec![Token::OpenParen, Token::Value(Value::Label("setq".to_string())), Token::Value(Value::Number(13.0)), Token::OpenParen, Token::Value(Value::Number(0.123)), Token::CloseParen, Token::CloseParen],
-            lex("(setq 13. (.123))".chars().fuse()).unwrap());
+            tokens("(setq 13. (.123))"));
     }
 
     #[test]
     fn test_lex_8() {
         assert_eq!(
             vec![Token::Value(Value::True), Token::Value(Value::Nil)],
-            lex("t nil".chars().fuse()).unwrap());
+            tokens("t nil"));
     }
 
     #[test]
     fn test_lex_string_1() {
         assert_eq!(
             vec![Token::Value(Value::String("ab12390noeu0voaeut,.hp\"oeuhtn".to_owned()))],
-            lex("\"ab12390noeu0voaeut,.hp\\\"oeuhtn\"".chars().fuse()).unwrap());
+            tokens("\"ab12390noeu0voaeut,.hp\\\"oeuhtn\""));
     }
 
     #[test]
     fn test_lex_string_2() {
         assert_eq!(
             vec![Token::Value(Value::String("\t\n\\n".to_owned()))],
-            lex("\"\\t\\n\\\\n\"".chars().fuse()).unwrap());
+            tokens("\"\\t\\n\\\\n\""));
     }
 
     #[test]
     fn test_lex_string_3() {
         assert_eq!(
             vec![Token::Value(Value::String("".to_owned()))],
-            lex("\"\"".chars().fuse()).unwrap());
+            tokens("\"\""));
     }
 
     #[test]
     fn test_lex_string_4() {
         assert_eq!(
             vec![Token::Value(Value::String("abcdefg".to_owned()))],
-            lex("\"abcdefg\"".chars().fuse()).unwrap());
+            tokens("\"abcdefg\""));
+    }
+
+    #[test]
+    fn test_lex_string_escapes() {
+        assert_eq!(
+            vec![Token::Value(Value::String("a\rb\0c".to_owned())),
+                 Token::Value(Value::String("AλA".to_owned())),
+                 Token::Value(Value::String("\u{1F600}!".to_owned()))],
+            tokens("\"a\\rb\\0c\" \"\\x41;\\u{3bb}\\x041;\" \"\\u{1f600}!\""));
+    }
+
+    #[test]
+    fn test_lex_string_continuation() {
+        assert_eq!(
+            vec![Token::Value(Value::String("one two".to_owned())),
+                 Token::Value(Value::String("ab".to_owned()))],
+            tokens("\"one \\\n     two\" \"a\\  \r\n\tb\""));
+    }
+
+    #[test]
+    fn test_lex_panic_escapes() {
+        for s in &["\"\\x41\"", "\"\\x;\"", "\"\\xg;\"", "\"\\u41\"", "\"\\u{d800}\"",
+                   "\"\\u{1000000}\"", "\"\\ a\"", "\"\\q\"", "\"\\x41"] {
+            assert!(lex(s.chars().fuse(), None).is_err(), "{}", s);
+        }
     }
 
     #[test]
     fn test_lex_label() {
         assert_eq!(
             vec![Token::Value(Value::Label("ns:xx/oeu-aoeu++".to_owned()))],
-            lex("ns:xx/oeu-aoeu++".chars().fuse()).unwrap());
+            tokens("ns:xx/oeu-aoeu++"));
+    }
+
+    #[test]
+    fn test_lex_dot() {
+        assert_eq!(
+            vec![Token::OpenParen, Token::Value(Value::Label("a".to_owned())), Token::Dot,
+                 Token::Value(Value::Label("b.c".to_owned())), Token::CloseParen],
+            tokens("(a . b.c)"));
+        assert_eq!(vec![Token::Value(Value::Label("..".to_owned()))], tokens(".."));
     }
 
     #[test]
     fn test_lex_panic_1() {
-        assert!(lex("13`()".chars().fuse()).is_err());
+        assert!(lex("13`()".chars().fuse(), None).is_err());
     }
 
     #[test]
     fn test_lex_panic_2() {
-        assert!(lex("13.'a)".chars().fuse()).is_err());
+        assert!(lex("13.'a)".chars().fuse(), None).is_err());
     }
 
     #[test]
     fn test_lex_panic_3() {
-        assert!(lex("\"\\a\"".chars().fuse()).is_err());
+        assert!(lex("\"\\a\"".chars().fuse(), None).is_err());
     }
 
     #[test]
     fn test_lex_panic_4() {
-        assert!(lex("\"\\\"".chars().fuse()).is_err());
+        assert!(lex("\"\\\"".chars().fuse(), None).is_err());
+    }
+
+    #[test]
+    fn test_lex_error_location() {
+        let e = lex("(a\n  13`b)".chars().fuse(), None).unwrap_err();
+        assert_eq!(ErrorKind::Lex, e.kind);
+        assert_eq!(Some(Location { file: None, line: 2, column: 5 }), e.location);
+    }
+
+    #[test]
+    fn test_lex_error_location_string() {
+        let e = lex("a\n \"abc".chars().fuse(), None).unwrap_err();
+        assert_eq!(Some(Location { file: None, line: 2, column: 2 }), e.location);
+    }
+
+    #[test]
+    fn test_lex_locations() {
+        let file: Rc<str> = Rc::from("f.scib");
+        let loc = |line, column| Location { file: Some(file.clone()), line, column };
+        assert_eq!(
+            vec![(Token::OpenParen, loc(1, 1)),
+                 (Token::Value(Value::Label("ab".to_owned())), loc(1, 2)),
+                 (Token::Quote, loc(2, 3)),
+                 (Token::Value(Value::String("x y".to_owned())), loc(2, 4)),
+                 (Token::CloseParen, loc(2, 9))],
+            lex("(ab\n  '\"x y\")".chars().fuse(), Some(file.clone())).unwrap());
+    }
+
+    #[test]
+    fn test_lex_line_comment() {
+        assert_eq!(
+            vec![Token::OpenParen, Token::Value(Value::Label("a".to_owned())), Token::Value(Value::Integer(1)),
+                 Token::Value(Value::Label("b".to_owned())), Token::CloseParen],
+            tokens("(a 1; comment (\n b) ; trailing"));
+    }
+
+    #[test]
+    fn test_lex_block_comment() {
+        assert_eq!(
+            vec![Token::Value(Value::Label("a".to_owned())), Token::Value(Value::Label("b".to_owned()))],
+            tokens("a #| one #| two |# still one ) |#b"));
+    }
+
+    #[test]
+    fn test_lex_datum_comment() {
+        assert_eq!(
+            vec![Token::DatumComment, Token::Value(Value::Label("a".to_owned())),
+                 Token::Value(Value::Label("#c".to_owned()))],
+            tokens("#;a #c"));
+    }
+
+    #[test]
+    fn test_lex_char() {
+        assert_eq!(
+            vec![Token::OpenParen, Token::Value(Value::Char('a')), Token::Value(Value::Char(' ')),
+                 Token::Value(Value::Char('\n')), Token::Value(Value::Char('A')),
+                 Token::Value(Value::Char('x')), Token::Value(Value::Char('(')),
+                 Token::Value(Value::Char(' ')), Token::Value(Value::Char(')')), Token::CloseParen],
+            tokens("(#\\a #\\space #\\newline #\\x41 #\\x #\\( #\\  #\\))"));
+    }
+
+    #[test]
+    fn test_lex_panic_char() {
+        assert!(lex("#\\".chars().fuse(), None).is_err());
+        assert!(lex("#\\spaces".chars().fuse(), None).is_err());
+        assert!(lex("#\\xd800".chars().fuse(), None).is_err());
+    }
+
+    #[test]
+    fn test_lex_panic_block_comment() {
+        assert!(lex("#| #| |#".chars().fuse(), None).is_err());
+    }
+
+    #[test]
+    fn test_lex_integer() {
+        assert_eq!(
+            vec![Token::Value(Value::Integer(-8)), Token::Value(Value::Integer(9223372036854775807)),
+                 Token::Value(Value::BigInt("9223372036854775808".parse().unwrap())), Token::Value(Value::Number(8.0))],
+            tokens("-8 9223372036854775807 9223372036854775808 8."));
+    }
+
+    #[test]
+    fn test_lex_radix() {
+        assert_eq!(
+            vec![Token::Value(Value::Integer(31)), Token::Value(Value::Integer(10)),
+                 Token::Value(Value::Integer(-8)), Token::Value(Value::Integer(12)),
+                 Token::Value(Value::Rational("1/2".parse().unwrap())),
+                 Token::Value(Value::BigInt("18446744073709551615".parse().unwrap()))],
+            tokens("#x1F #b1010 #o-10 #d12 #xA/14 #XFFFFFFFFFFFFFFFF"));
+    }
+
+    #[test]
+    fn test_lex_radix_prefixed_labels() {
+        assert_eq!(
+            vec![Token::Value(Value::Integer(31)),
+                 Token::Value(Value::Label("#xylophone".to_owned())),
+                 Token::Value(Value::Label("#define".to_owned())),
+                 Token::Value(Value::Label("#bar".to_owned())),
+                 Token::Value(Value::Label("#o".to_owned()))],
+            tokens("#x1F #xylophone #define #bar #o"));
+    }
+
+    #[test]
+    fn test_lex_digit_separators() {
+        assert_eq!(
+            vec![Token::Value(Value::Integer(1000000)), Token::Value(Value::Integer(0xffff)),
+                 Token::Value(Value::Number(1000.5)),
+                 Token::Value(Value::Label("_1".to_owned())), Token::Value(Value::Label("1_".to_owned())),
+                 Token::Value(Value::Label("1__0".to_owned()))],
+            tokens("1_000_000 #xff_ff 1_000.5 _1 1_ 1__0"));
+    }
+
+    #[test]
+    fn test_lex_exponent() {
+        assert_eq!(
+            vec![Token::Value(Value::Number(1000.0)), Token::Value(Value::Number(1000.0)),
+                 Token::Value(Value::Number(1000.0)), Token::Value(Value::Number(0.001)),
+                 Token::Value(Value::Number(500.0)), Token::Value(Value::Number(1500.0)),
+                 Token::Value(Value::Number(-1000.0)), Token::Value(Value::Label("e3".to_owned()))],
+            tokens("1e3 1E3 1e+3 1e-3 .5e3 1.5e3 -1.e3 e3"));
+    }
+
+    #[test]
+    fn test_lex_special_floats() {
+        let toks = tokens("+inf.0 -inf.0 +nan.0 -nan.0 inf nan");
+        assert_eq!(Token::Value(Value::Number(f64::INFINITY)), toks[0]);
+        assert_eq!(Token::Value(Value::Number(f64::NEG_INFINITY)), toks[1]);
+        match (&toks[2], &toks[3]) {
+            (&Token::Value(Value::Number(a)), &Token::Value(Value::Number(b))) => assert!(a.is_nan() && b.is_nan()),
+            _ => panic!(),
+        }
+        assert_eq!(Token::Value(Value::Label("inf".to_owned())), toks[4]);
+        assert_eq!(Token::Value(Value::Label("nan".to_owned())), toks[5]);
+    }
+
+    #[test]
+    fn test_lex_panic_radix() {
+        assert!(lex("#b102".chars().fuse(), None).is_err());
+        assert!(lex("#x-".chars().fuse(), None).is_err());
+        assert!(lex("#x1.5".chars().fuse(), None).is_err());
+    }
+
+    #[test]
+    fn test_lex_rational() {
+        assert_eq!(
+            vec![Token::Value(Value::Rational("1/3".parse().unwrap())),
+                 Token::Value(Value::Rational("-1/2".parse().unwrap())),
+                 Token::Value(Value::Integer(2)),
+                 Token::Value(Value::Label("1/0".to_owned())),
+                 Token::Value(Value::Label("/".to_owned()))],
+            tokens("1/3 -2/4 4/2 1/0 /"));
     }
 }
@@ -1,10 +1,32 @@
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+#[macro_use]
+extern crate serde_derive;
+
+pub mod error;
+pub use error::*;
+pub mod bignum;
+pub use bignum::{BigInt, Rational};
 pub mod types;
 pub use types::*;
 pub mod instance;
 pub use instance::*;
+pub mod reader;
+pub use reader::*;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "serde")]
+pub use ser::{to_value, Serializer};
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "serde")]
+pub use de::{from_str, from_value};
+mod source_map;
 mod lex;
 mod parse;
 mod eval;
+mod numeric;
 mod builtins;
 
 #[cfg(test)]
@@ -0,0 +1,108 @@
+extern crate scib;
+
+use scib::{ErrorKind, Reader, Scib};
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+fn usage() -> ! {
+    eprintln!("Usage: scib [-e EXPR | FILE...]");
+    process::exit(2);
+}
+
+/// Checks whether `input` can be evaluated without waiting for more lines,
+/// i.e. whether reading it never runs out partway through a datum.  Input
+/// with a hard syntax error counts as complete so the error is reported.
+fn is_complete(input: &str) -> bool {
+    let mut reader = Reader::new(input.chars(), None);
+    loop {
+        match reader.read() {
+            Ok(Some(_)) => {},
+            Ok(None) => return true,
+            Err(e) => return e.kind != ErrorKind::Incomplete,
+        }
+    }
+}
+
+fn repl(scib: &mut Scib) {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut input = String::new();
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { "  " });
+        io::stdout().flush().unwrap();
+        match lines.next() {
+            Some(Ok(line)) => {
+                input.push_str(&line);
+                input.push('\n');
+            },
+            Some(Err(e)) => {
+                eprintln!("error: {}", e);
+                return;
+            },
+            None => {
+                println!();
+                return;
+            },
+        }
+        if !is_complete(&input) {
+            continue;
+        }
+        if !input.trim().is_empty() {
+            match scib.eval(&input) {
+                Ok(v) => println!("{}", v),
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+        input.clear();
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut scib = Scib::new();
+    if args.is_empty() {
+        repl(&mut scib);
+        return;
+    }
+    if args[0] == "-e" {
+        if args.len() != 2 {
+            usage();
+        }
+        match scib.eval(&args[1]) {
+            Ok(v) => println!("{}", v),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            },
+        }
+        return;
+    }
+    for file in &args {
+        if let Err(e) = scib.eval_file(file) {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_complete() {
+        assert!(is_complete("(+ 1 2)"));
+        assert!(is_complete("x"));
+        assert!(!is_complete("(+ 1\n"));
+        assert!(is_complete("(+ 1\n 2)\n"));
+        assert!(!is_complete("\"(\" (a"));
+        assert!(is_complete("\")\" a"));
+        assert!(is_complete("(a ; )\n)"));
+        assert!(!is_complete("#| ) |# (a"));
+        assert!(is_complete("a #| #| |# ( |#"));
+        assert!(!is_complete("a #| #| |# ("));
+        assert!(!is_complete("\"abc"));
+        assert!(is_complete("(list #\\( #\\\")"));
+    }
+}
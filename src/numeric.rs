@@ -0,0 +1,467 @@
+use std::cmp::Ordering;
+use std::rc::Rc;
+use types::Value;
+use bignum::{BigInt, Rational};
+use error::{Result, Error, ErrorKind};
+
+/// The most bits `expt` will produce for an exact result, so a huge power
+/// is an error instead of exhausting memory.
+const MAX_EXPT_BITS: u64 = 1 << 20;
+
+/// A number taken out of a `Value` for arithmetic.  Operations on exact
+/// numbers stay exact, growing into big integers and rationals as needed,
+/// and only produce floats when one of the operands is a float.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    Big(BigInt),
+    Rational(Rational),
+    Float(f64),
+}
+
+impl Number {
+    /// Extracts a number from `v`, naming `function` in the error if `v`
+    /// isn't one.
+    pub fn from_value(function: &str, v: &Value) -> Result<Number> {
+        match *v {
+            Value::Integer(i) => Ok(Number::Integer(i)),
+            Value::BigInt(ref i) => Ok(Number::Big(i.clone())),
+            Value::Rational(ref r) => Ok(Number::Rational(r.clone())),
+            Value::Number(n) => Ok(Number::Float(n)),
+            ref v => Err(Error::new(ErrorKind::Type,
+                                    format!("{}'s arguments must all be numbers, found '{:?}'", function, v))),
+        }
+    }
+
+    pub fn into_value(self) -> Rc<Value> {
+        Rc::new(Value::from(self))
+    }
+
+    /// Puts an exact integer in its smallest representation.
+    pub fn from_bigint(i: BigInt) -> Number {
+        match i.to_i64() {
+            Some(i) => Number::Integer(i),
+            None => Number::Big(i),
+        }
+    }
+
+    /// Puts an exact fraction in its smallest representation.
+    pub fn from_rational(r: Rational) -> Number {
+        if r.is_integer() {
+            Number::from_bigint(r.numer().clone())
+        } else {
+            Number::Rational(r)
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            Number::Integer(i) => i as f64,
+            Number::Big(ref i) => i.to_f64(),
+            Number::Rational(ref r) => r.to_f64(),
+            Number::Float(n) => n,
+        }
+    }
+
+    fn to_rational(&self) -> Option<Rational> {
+        match *self {
+            Number::Integer(i) => Some(Rational::from_integer(BigInt::from(i))),
+            Number::Big(ref i) => Some(Rational::from_integer(i.clone())),
+            Number::Rational(ref r) => Some(r.clone()),
+            Number::Float(_) => None,
+        }
+    }
+
+    fn combine(self, other: Number, int: fn(i64, i64) -> Option<i64>,
+               exact: fn(&Rational, &Rational) -> Rational, float: fn(f64, f64) -> f64) -> Number {
+        if let (&Number::Integer(a), &Number::Integer(b)) = (&self, &other) {
+            if let Some(i) = int(a, b) {
+                return Number::Integer(i);
+            }
+        }
+        match (self.to_rational(), other.to_rational()) {
+            (Some(a), Some(b)) => Number::from_rational(exact(&a, &b)),
+            _ => Number::Float(float(self.to_f64(), other.to_f64())),
+        }
+    }
+
+    /// Compares numerically, so `1` and `1.0` are equal.  Returns `None`
+    /// if either is NaN.  A finite float is compared with an exact number
+    /// by its exact value, so no precision is lost to rounding.
+    pub fn compare(&self, other: &Number) -> Option<Ordering> {
+        let exact = |n: &Number| match *n {
+            Number::Float(f) => Rational::from_f64(f),
+            _ => n.to_rational(),
+        };
+        match (self, other) {
+            (&Number::Integer(a), &Number::Integer(b)) => Some(a.cmp(&b)),
+            (&Number::Float(a), &Number::Float(b)) => a.partial_cmp(&b),
+            _ => match (exact(self), exact(other)) {
+                (Some(a), Some(b)) => Some(a.cmp(&b)),
+                // The float is an infinity or NaN, so only its sign matters.
+                (Some(_), None) => 0f64.partial_cmp(&other.to_f64()),
+                _ => self.to_f64().partial_cmp(&0.0),
+            },
+        }
+    }
+
+    pub fn add(self, other: Number) -> Number {
+        self.combine(other, i64::checked_add, |a, b| a + b, |a, b| a + b)
+    }
+
+    pub fn sub(self, other: Number) -> Number {
+        self.combine(other, i64::checked_sub, |a, b| a - b, |a, b| a - b)
+    }
+
+    pub fn mul(self, other: Number) -> Number {
+        self.combine(other, i64::checked_mul, |a, b| a * b, |a, b| a * b)
+    }
+
+    fn is_zero(&self) -> bool {
+        // Exact numbers are kept normalized, so zero is always an Integer.
+        *self == Number::Integer(0) || *self == Number::Float(0.0)
+    }
+
+    pub fn is_exact(&self) -> bool {
+        !matches!(*self, Number::Float(_))
+    }
+
+    /// The number of bits in the magnitude of an exact number, or of the
+    /// larger of its numerator and denominator.
+    fn bit_len(&self) -> u64 {
+        match *self {
+            Number::Integer(i) => 64 - i.unsigned_abs().leading_zeros() as u64,
+            Number::Big(ref b) => b.bit_len() as u64,
+            Number::Rational(ref r) => r.numer().bit_len().max(r.denom().bit_len()) as u64,
+            Number::Float(_) => 64,
+        }
+    }
+
+    /// Fails if `self`, the divisor of `function`, is zero.
+    fn check_divisor(&self, function: &str) -> Result<()> {
+        if self.is_zero() {
+            Err(Error::new(ErrorKind::DivisionByZero, format!("{}: division by zero", function)))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn div(self, other: Number) -> Result<Number> {
+        other.check_divisor("/")?;
+        Ok(self.combine(other,
+                        |a, b| match a.checked_rem(b) {
+                            Some(0) => a.checked_div(b),
+                            _ => None,
+                        },
+                        |a, b| a * &b.recip(),
+                        |a, b| a / b))
+    }
+
+    pub fn abs(self) -> Number {
+        match self {
+            Number::Integer(i) => match i.checked_abs() {
+                Some(i) => Number::Integer(i),
+                None => Number::Big(BigInt::from(i).abs()),
+            },
+            Number::Big(i) => Number::Big(i.abs()),
+            Number::Rational(r) => Number::Rational(Rational::new(r.numer().abs(), r.denom().clone())),
+            Number::Float(n) => Number::Float(n.abs()),
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.compare(&Number::Integer(0)) == Some(Ordering::Less)
+    }
+
+    /// Rounds to an integer.  Exact numbers give exact integers and floats
+    /// give integral floats.
+    pub fn round(self, rounding: Rounding) -> Number {
+        match self {
+            Number::Integer(_) | Number::Big(_) => self,
+            Number::Rational(r) => {
+                let floor = r.floor();
+                let one = BigInt::one();
+                let ceiling = &floor + &one;
+                Number::from_bigint(match rounding {
+                    Rounding::Floor => floor,
+                    Rounding::Ceiling => ceiling,
+                    Rounding::Truncate if r.numer().is_negative() => ceiling,
+                    Rounding::Truncate => floor,
+                    Rounding::Round => {
+                        // Compare the fractional part with 1/2, breaking
+                        // ties towards the even integer.
+                        let frac = &r - &Rational::from_integer(floor.clone());
+                        let half = Rational::new(one, BigInt::from(2));
+                        match frac.cmp(&half) {
+                            Ordering::Less => floor,
+                            Ordering::Greater => ceiling,
+                            Ordering::Equal if floor.is_even() => floor,
+                            Ordering::Equal => ceiling,
+                        }
+                    },
+                })
+            },
+            Number::Float(n) => Number::Float(match rounding {
+                Rounding::Floor => n.floor(),
+                Rounding::Ceiling => n.ceil(),
+                Rounding::Round => n.round_ties_even(),
+                Rounding::Truncate => n.trunc(),
+            }),
+        }
+    }
+
+    /// Checks that `self` is an argument `function` can treat as an
+    /// integer: an exact integer, or a float with no fractional part.
+    fn check_integer(&self, function: &str) -> Result<()> {
+        match *self {
+            Number::Integer(_) | Number::Big(_) => Ok(()),
+            Number::Float(n) if n.is_finite() && n.fract() == 0.0 => Ok(()),
+            _ => Err(Error::new(ErrorKind::Type,
+                                format!("{} requires integers, found '{}'", function, Value::from(self.clone())))),
+        }
+    }
+
+    fn to_bigint(&self) -> Option<BigInt> {
+        match *self {
+            Number::Integer(i) => Some(BigInt::from(i)),
+            Number::Big(ref i) => Some(i.clone()),
+            _ => None,
+        }
+    }
+
+    /// Divides integers, truncating the quotient towards zero.  Returns
+    /// the quotient and the remainder, which has the sign of `self`.
+    fn div_rem(self, other: Number, function: &str) -> Result<(Number, Number)> {
+        self.check_integer(function)?;
+        other.check_integer(function)?;
+        other.check_divisor(function)?;
+        if let (&Number::Integer(a), &Number::Integer(b)) = (&self, &other) {
+            if let (Some(q), Some(r)) = (a.checked_div(b), a.checked_rem(b)) {
+                return Ok((Number::Integer(q), Number::Integer(r)));
+            }
+        }
+        match (self.to_bigint(), other.to_bigint()) {
+            (Some(a), Some(b)) => {
+                let (q, r) = a.div_rem(&b);
+                Ok((Number::from_bigint(q), Number::from_bigint(r)))
+            },
+            _ => {
+                let (a, b) = (self.to_f64(), other.to_f64());
+                let r = a % b;
+                Ok((Number::Float((a - r) / b), Number::Float(r)))
+            },
+        }
+    }
+
+    pub fn quotient(self, other: Number) -> Result<Number> {
+        Ok(self.div_rem(other, "quotient")?.0)
+    }
+
+    pub fn rem(self, other: Number) -> Result<Number> {
+        Ok(self.div_rem(other, "rem")?.1)
+    }
+
+    /// The remainder of floored division, which has the sign of `other`.
+    pub fn modulo(self, other: Number) -> Result<Number> {
+        let r = self.div_rem(other.clone(), "mod")?.1;
+        if !r.is_zero() && r.is_negative() != other.is_negative() {
+            Ok(r.add(other))
+        } else {
+            Ok(r)
+        }
+    }
+
+    /// Raises `self` to `power`.  An exact number raised to an integer
+    /// stays exact, and anything else is computed with floats.
+    pub fn expt(self, power: Number) -> Result<Number> {
+        let e = match power {
+            Number::Integer(e) if self.is_exact() => e,
+            Number::Integer(e) => return Ok(Number::Float(self.to_f64().powf(e as f64))),
+            _ => return Ok(Number::Float(self.to_f64().powf(power.to_f64()))),
+        };
+        if e < 0 {
+            self.check_divisor("expt")?;
+        }
+        if self.bit_len() > 1 && self.bit_len().saturating_mul(e.unsigned_abs()) > MAX_EXPT_BITS {
+            return Err(Error::new(ErrorKind::Range, "expt: result is too large".to_string()));
+        }
+        let mut result = Number::Integer(1);
+        let mut base = self;
+        let mut n = e.unsigned_abs();
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.mul(base.clone());
+            }
+            n >>= 1;
+            if n > 0 {
+                base = base.clone().mul(base);
+            }
+        }
+        if e < 0 {
+            Number::Integer(1).div(result)
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// The square root, which is exact when `self` is the square of an
+    /// exact number small enough to check.  Negative numbers give NaN.
+    pub fn sqrt(self) -> Number {
+        match self {
+            Number::Integer(i) => if let Some(s) = exact_sqrt(i) {
+                return Number::Integer(s);
+            },
+            Number::Rational(ref r) => {
+                let root = |i: &BigInt| i.to_i64().and_then(exact_sqrt);
+                if let (Some(n), Some(d)) = (root(r.numer()), root(r.denom())) {
+                    return Number::Rational(Rational::new(BigInt::from(n), BigInt::from(d)));
+                }
+            },
+            _ => {},
+        }
+        Number::Float(self.to_f64().sqrt())
+    }
+}
+
+fn exact_sqrt(i: i64) -> Option<i64> {
+    if i < 0 {
+        return None;
+    }
+    // The float estimate can be off by one for large numbers.
+    let mut s = (i as f64).sqrt() as i64;
+    while s.checked_mul(s).is_none_or(|sq| sq > i) {
+        s -= 1;
+    }
+    while (s + 1).checked_mul(s + 1).is_some_and(|sq| sq <= i) {
+        s += 1;
+    }
+    if s * s == i { Some(s) } else { None }
+}
+
+/// How `Number::round` picks an integer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    Floor,
+    Ceiling,
+    /// To the nearest integer, with ties going to the even one.
+    Round,
+    Truncate,
+}
+
+impl From<Number> for Value {
+    fn from(n: Number) -> Value {
+        match n {
+            Number::Integer(i) => Value::Integer(i),
+            Number::Big(i) => Value::BigInt(i),
+            Number::Rational(r) => Value::Rational(r),
+            Number::Float(n) => Value::Number(n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rational(s: &str) -> Number {
+        Number::Rational(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_number_exact() {
+        assert_eq!(Number::Integer(5), Number::Integer(2).add(Number::Integer(3)));
+        assert_eq!(Number::Integer(-1), Number::Integer(2).sub(Number::Integer(3)));
+        assert_eq!(Number::Integer(6), Number::Integer(2).mul(Number::Integer(3)));
+        assert_eq!(Number::Integer(3), Number::Integer(6).div(Number::Integer(2)).unwrap());
+        assert_eq!(rational("5/2"), Number::Integer(5).div(Number::Integer(2)).unwrap());
+        assert_eq!(Number::Integer(1), rational("1/3").add(rational("2/3")));
+    }
+
+    #[test]
+    fn test_number_big() {
+        let big = Number::Integer(i64::MAX).add(Number::Integer(1));
+        assert_eq!(Number::Big("9223372036854775808".parse().unwrap()), big);
+        assert_eq!(Number::Integer(i64::MAX), big.clone().sub(Number::Integer(1)));
+        assert_eq!(Number::Integer(i64::MIN), big.clone().mul(Number::Integer(-1)));
+        assert_eq!(rational("9223372036854775808/3"), big.div(Number::Integer(3)).unwrap());
+    }
+
+    #[test]
+    fn test_number_compare() {
+        assert_eq!(Some(Ordering::Less), Number::Integer(1).compare(&Number::Integer(2)));
+        assert_eq!(Some(Ordering::Equal), Number::Integer(1).compare(&Number::Float(1.0)));
+        assert_eq!(Some(Ordering::Greater), rational("1/2").compare(&rational("1/3")));
+        assert_eq!(Some(Ordering::Less), Number::Integer(i64::MAX).compare(&Number::Big("9223372036854775808".parse().unwrap())));
+        assert_eq!(None, Number::Integer(1).compare(&Number::Float(f64::NAN)));
+        // 2^53 + 1 is not a float, so it must not round to 2^53 to compare.
+        let above = Number::Integer(9007199254740993);
+        assert_eq!(Some(Ordering::Greater), above.compare(&Number::Float(9007199254740992.0)));
+        assert_eq!(Some(Ordering::Less), Number::Float(9007199254740992.0).compare(&above));
+        assert_eq!(Some(Ordering::Equal), Number::Integer(9007199254740992).compare(&Number::Float(9007199254740992.0)));
+        assert_eq!(Some(Ordering::Greater), rational("1/3").compare(&Number::Float(1.0 / 3.0)));
+        let huge = Number::Big(format!("1{}", "0".repeat(400)).parse().unwrap());
+        assert_eq!(Some(Ordering::Less), huge.compare(&Number::Float(f64::INFINITY)));
+        assert_eq!(Some(Ordering::Less), Number::Float(f64::NEG_INFINITY).compare(&huge));
+    }
+
+    #[test]
+    fn test_number_promote() {
+        assert_eq!(Number::Float(3.5), Number::Integer(1).add(Number::Float(2.5)));
+        assert_eq!(Number::Float(0.75), rational("1/4").add(Number::Float(0.5)));
+    }
+
+    #[test]
+    fn test_number_division_by_zero() {
+        for zero in [Number::Integer(0), Number::Float(0.0), Number::Float(-0.0)] {
+            assert_eq!(ErrorKind::DivisionByZero, Number::Integer(1).div(zero.clone()).unwrap_err().kind);
+            assert_eq!(ErrorKind::DivisionByZero, Number::Integer(1).modulo(zero).unwrap_err().kind);
+        }
+        assert_eq!(ErrorKind::DivisionByZero, Number::Integer(0).expt(Number::Integer(-1)).unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_number_integer_division() {
+        let int = Number::Integer;
+        for &(a, b, q, r, m) in &[(7, 2, 3, 1, 1), (-7, 2, -3, -1, 1), (7, -2, -3, 1, -1), (-7, -2, 3, -1, -1)] {
+            assert_eq!(int(q), int(a).quotient(int(b)).unwrap());
+            assert_eq!(int(r), int(a).rem(int(b)).unwrap());
+            assert_eq!(int(m), int(a).modulo(int(b)).unwrap());
+        }
+        assert_eq!(Number::Big("9223372036854775808".parse().unwrap()), int(i64::MIN).quotient(int(-1)).unwrap());
+        assert_eq!(Number::Float(1.0), Number::Float(-7.0).modulo(int(2)).unwrap());
+        assert_eq!(ErrorKind::Type, rational("1/2").rem(int(2)).unwrap_err().kind);
+        assert_eq!(ErrorKind::Type, Number::Float(0.5).rem(int(2)).unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_number_round() {
+        for &(s, floor, ceiling, round, truncate) in &[("7/2", 3, 4, 4, 3), ("5/2", 2, 3, 2, 2),
+                                                       ("-5/2", -3, -2, -2, -2), ("-7/3", -3, -2, -2, -2)] {
+            let r = rational(s);
+            assert_eq!(Number::Integer(floor), r.clone().round(Rounding::Floor), "{}", s);
+            assert_eq!(Number::Integer(ceiling), r.clone().round(Rounding::Ceiling), "{}", s);
+            assert_eq!(Number::Integer(round), r.clone().round(Rounding::Round), "{}", s);
+            assert_eq!(Number::Integer(truncate), r.round(Rounding::Truncate), "{}", s);
+        }
+        assert_eq!(Number::Float(2.0), Number::Float(2.5).round(Rounding::Round));
+        assert_eq!(Number::Float(-3.0), Number::Float(-2.5).round(Rounding::Floor));
+    }
+
+    #[test]
+    fn test_number_expt_sqrt() {
+        assert_eq!(Number::Integer(1024), Number::Integer(2).expt(Number::Integer(10)).unwrap());
+        assert_eq!(rational("1/8"), Number::Integer(2).expt(Number::Integer(-3)).unwrap());
+        assert_eq!(rational("9/4"), rational("3/2").expt(Number::Integer(2)).unwrap());
+        assert_eq!(Number::Integer(1), Number::Integer(0).expt(Number::Integer(0)).unwrap());
+        assert_eq!(Number::Float(8.0), Number::Float(2.0).expt(Number::Integer(3)).unwrap());
+        assert_eq!(Number::Float(2.0), Number::Integer(4).expt(rational("1/2")).unwrap());
+        assert_eq!(Number::Integer(1), Number::Integer(-1).expt(Number::Integer(i64::MAX - 1)).unwrap());
+        assert_eq!(ErrorKind::Range, Number::Integer(2).expt(Number::Integer(i64::MAX)).unwrap_err().kind);
+        assert_eq!(ErrorKind::Range, rational("1/2").expt(Number::Integer(i64::MIN)).unwrap_err().kind);
+        assert!(Number::Integer(2).expt(Number::Integer(10_000)).is_ok());
+        assert_eq!(Number::Integer(3037000499), Number::Integer(9223372030926249001).sqrt());
+        assert_eq!(rational("2/3"), rational("4/9").sqrt());
+        assert_eq!(Number::Float(2f64.sqrt()), Number::Integer(2).sqrt());
+        assert!(Number::Integer(-4).sqrt().to_f64().is_nan());
+    }
+}
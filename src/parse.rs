@@ -1,82 +1,156 @@
 use lex::Token;
 use types::*;
 use std::rc::Rc;
-use std::io::{Result, Error, ErrorKind};
+use error::{Result, Error, ErrorKind, Location};
+use source_map::SourceMap;
 
-fn parse_token<I: Iterator<Item = Token>>(token: Token, tokens: &mut I, in_paren: bool, in_backquote: i32) -> Result<Option<Rc<Value>>> {
-    match token {
+/// Parses the datum following a prefix such as `'` at `location`, failing
+/// with `message` if there is none.  Running out of tokens is reported as
+/// `Incomplete` since more input could supply the datum.
+fn parse_prefixed<I: Iterator<Item = Result<(Token, Location)>>>(tokens: &mut I, map: &mut SourceMap, location: &Location, message: &str, open_paren: Option<&Location>, in_backquote: i32) -> Result<Rc<Value>> {
+    let (v, l) = match tokens.next() {
+        Some(token) => token?,
+        None => return Err(Error::new(ErrorKind::Incomplete, message).at(location.clone())),
+    };
+    parse_token(v, l, tokens, map, open_paren, in_backquote)?
+        .ok_or_else(|| Error::new(ErrorKind::Parse, message).at(location.clone()))
+}
+
+fn unclosed_paren(location: &Location) -> Error {
+    Error::new(ErrorKind::Incomplete, "Open parenthesis without accompanying closing parenthesis.")
+        .at(location.clone())
+}
+
+fn parse_token<I: Iterator<Item = Result<(Token, Location)>>>(token: Token, location: Location, tokens: &mut I, map: &mut SourceMap, open_paren: Option<&Location>, in_backquote: i32) -> Result<Option<Rc<Value>>> {
+    let value = match token {
         Token::Value(v) => {
-            Ok(Some(Rc::new(v)))
+            v
         },
         Token::Backquote => {
-            fn err() -> Error { Error::new(ErrorKind::InvalidInput,
-                                           format!("Backquote without accompanying quoted data")) };
-            let v = try!(tokens.next().ok_or_else(err));
-            let e = try!(try!(parse_token(v, tokens, in_paren, in_backquote + 1)).ok_or_else(err));
-            Ok(Some(Rc::new(Value::Backquote(e))))
+            Value::Backquote(parse_prefixed(tokens, map, &location, "Backquote without accompanying quoted data",
+                                            open_paren, in_backquote + 1)?)
         },
         Token::Unquote => {
             if in_backquote <= 0 {
-                return Err(Error::new(ErrorKind::InvalidInput,
-                                      format!("Unquote without accompanying backquote")));
+                return Err(Error::new(ErrorKind::Parse,
+                                      "Unquote without accompanying backquote").at(location));
             }
-            fn err() -> Error { Error::new(ErrorKind::InvalidInput,
-                                           format!("Unquote without accompanying quoted data")) };
-            let v = try!(tokens.next().ok_or_else(err));
-            let e = try!(try!(parse_token(v, tokens, in_paren, in_backquote - 1)).ok_or_else(err));
-            Ok(Some(Rc::new(Value::Unquote(e))))
+            Value::Unquote(parse_prefixed(tokens, map, &location, "Unquote without accompanying quoted data",
+                                          open_paren, in_backquote - 1)?)
         },
         Token::UnquoteList => {
             if in_backquote <= 0 {
-                return Err(Error::new(ErrorKind::InvalidInput,
-                                      format!("Unquote without accompanying backquote")));
+                return Err(Error::new(ErrorKind::Parse,
+                                      "Unquote without accompanying backquote").at(location));
             }
-            fn err() -> Error { Error::new(ErrorKind::InvalidInput,
-                                           format!("Unquote without accompanying quoted data")) };
-            let v = try!(tokens.next().ok_or_else(err));
-            let e = try!(try!(parse_token(v, tokens, in_paren, in_backquote - 1)).ok_or_else(err));
-            Ok(Some(Rc::new(Value::UnquoteList(e))))
+            Value::UnquoteList(parse_prefixed(tokens, map, &location, "Unquote without accompanying quoted data",
+                                              open_paren, in_backquote - 1)?)
         },
         Token::Quote => {
-            fn err() -> Error { Error::new(ErrorKind::InvalidInput,
-                                           format!("Quote without accompanying quoted data")) };
-            let v = try!(tokens.next().ok_or_else(err));
-            let e = try!(try!(parse_token(v, tokens, in_paren, in_backquote)).ok_or_else(err));
-            Ok(Some(Rc::new(Value::Quote(e))))
+            Value::Quote(parse_prefixed(tokens, map, &location, "Quote without accompanying quoted data",
+                                        open_paren, in_backquote)?)
         },
         Token::OpenParen => {
             let mut e = Vec::new();
-            try!(parse_(tokens, &mut e, true, in_backquote));
-            Ok(Some(Rc::new(Value::List(e))))
+            let tail = parse_list(tokens, map, &mut e, &location, in_backquote)?
+                .unwrap_or_else(|| Rc::new(Value::Nil));
+            if e.is_empty() {
+                Value::Nil
+            } else {
+                let car = e.remove(0);
+                let cdr = e.into_iter().rev().fold(tail, |cdr, car| Value::cons(car, cdr));
+                Value::Pair(Pair::new(car, cdr))
+            }
+        },
+        Token::Dot => {
+            return Err(Error::new(ErrorKind::Parse, "Dot outside of a list").at(location));
+        },
+        Token::DatumComment => {
+            parse_prefixed(tokens, map, &location, "Datum comment without accompanying datum",
+                           open_paren, in_backquote)?;
+            return match tokens.next() {
+                Some(token) => {
+                    let (v, l) = token?;
+                    parse_token(v, l, tokens, map, open_paren, in_backquote)
+                },
+                None => match open_paren {
+                    Some(location) => Err(unclosed_paren(location)),
+                    None => Ok(None),
+                },
+            };
         },
         Token::CloseParen =>
-            if in_paren {
+            return if open_paren.is_some() {
                 Ok(None)
             } else {
-                Err(Error::new(ErrorKind::InvalidInput,
-                               format!("Closing parenthesis without accompanying open parenthesis.")))
+                Err(Error::new(ErrorKind::Parse,
+                               "Closing parenthesis without accompanying open parenthesis.")
+                    .at(location))
             },
-    }
-    
+    };
+    let value = Rc::new(value);
+    map.insert(&value, location);
+    Ok(Some(value))
 }
 
-fn parse_<I: Iterator<Item = Token>>(tokens: &mut I, exprs: &mut Vec<Rc<Value>>, in_paren: bool, in_backquote: i32) -> Result<()> {
+/// Parses the elements of a list into `exprs`, returning the datum after
+/// the dot if the list is dotted.
+fn parse_list<I: Iterator<Item = Result<(Token, Location)>>>(tokens: &mut I, map: &mut SourceMap, exprs: &mut Vec<Rc<Value>>, open_paren: &Location, in_backquote: i32) -> Result<Option<Rc<Value>>> {
     while let Some(token) = tokens.next() {
-        match try!(parse_token(token, tokens, in_paren, in_backquote)) {
+        let (token, location) = token?;
+        if let Token::Dot = token {
+            if exprs.is_empty() {
+                return Err(Error::new(ErrorKind::Parse, "Dot without a preceding datum").at(location));
+            }
+            let tail = parse_prefixed(tokens, map, &location, "Dot without a following datum",
+                                      Some(open_paren), in_backquote)?;
+            return parse_dotted_end(tokens, map, open_paren, in_backquote).map(|()| Some(tail));
+        }
+        match parse_token(token, location, tokens, map, Some(open_paren), in_backquote)? {
             Some(t) => exprs.push(t),
-            None => return Ok(()),
+            None => return Ok(None),
+        }
+    }
+    Err(unclosed_paren(open_paren))
+}
+
+/// Expects the closing parenthesis of a dotted list, skipping datum
+/// comments.
+fn parse_dotted_end<I: Iterator<Item = Result<(Token, Location)>>>(tokens: &mut I, map: &mut SourceMap, open_paren: &Location, in_backquote: i32) -> Result<()> {
+    while let Some(token) = tokens.next() {
+        match token? {
+            (Token::CloseParen, _) => return Ok(()),
+            (Token::DatumComment, location) => {
+                parse_prefixed(tokens, map, &location, "Datum comment without accompanying datum",
+                               Some(open_paren), in_backquote)?;
+            },
+            (_, location) => return Err(Error::new(ErrorKind::Parse,
+                                                   "Expected only one datum after a dot").at(location)),
         }
     }
-    if in_paren {
-        Err(Error::new(ErrorKind::InvalidInput, "Open parenthesis without accompanying closing parenthesis.".to_owned()))
-    } else {
-        Ok(())
+    Err(unclosed_paren(open_paren))
+}
+
+/// Builds the next top-level value from `tokens`, recording where it came
+/// from in `map`.  Returns `None` once the tokens run out.
+pub fn parse_one<I: Iterator<Item = Result<(Token, Location)>>>(tokens: &mut I, map: &mut SourceMap) -> Result<Option<Rc<Value>>> {
+    match tokens.next() {
+        Some(token) => {
+            let (token, location) = token?;
+            parse_token(token, location, tokens, map, None, 0)
+        },
+        None => Ok(None),
     }
 }
 
-pub fn parse(tokens: Vec<Token>) -> Result<Vec<Rc<Value>>> {
+/// Builds values from all of `tokens`, recording where each one came from
+/// in `map`.
+#[cfg(test)]
+pub fn parse<I: Iterator<Item = Result<(Token, Location)>>>(mut tokens: I, map: &mut SourceMap) -> Result<Vec<Rc<Value>>> {
     let mut exprs = Vec::new();
-    try!(parse_(&mut tokens.into_iter(), &mut exprs, false, 0));
+    while let Some(expr) = parse_one(&mut tokens, map)? {
+        exprs.push(expr);
+    }
     Ok(exprs)
 }
 
@@ -84,18 +158,23 @@ pub fn parse(tokens: Vec<Token>) -> Result<Vec<Rc<Value>>> {
 mod tests {
     use super::*;
 
+    fn parse_tokens(tokens: Vec<Token>) -> Result<Vec<Rc<Value>>> {
+        let location = Location { file: None, line: 1, column: 1 };
+        parse(tokens.into_iter().map(|t| Ok((t, location.clone()))), &mut SourceMap::new())
+    }
+
     #[test]
     fn test_parse_1() {
         assert_eq!(
-            vec![Rc::new(Value::List(vec![]))],
-            parse(vec![Token::OpenParen, Token::CloseParen]).unwrap());
+            vec![Value::list(vec![])],
+            parse_tokens(vec![Token::OpenParen, Token::CloseParen]).unwrap());
     }
 
     #[test]
     fn test_parse_2() {
         assert_eq!(
-            vec![Rc::new(Value::List(vec![Rc::new(Value::Label("abc".to_owned()))]))],
-            parse(vec![Token::OpenParen,
+            vec![Value::list(vec![Rc::new(Value::Label("abc".to_owned()))])],
+            parse_tokens(vec![Token::OpenParen,
                        Token::Value(Value::Label("abc".to_owned())),
                        Token::CloseParen]).unwrap());
     }
@@ -103,9 +182,9 @@ mod tests {
     #[test]
     fn test_parse_3() {
         assert_eq!(
-            vec![Rc::new(Value::List(vec![Rc::new(Value::Label("abc".to_owned()))])),
-                 Rc::new(Value::List(vec![Rc::new(Value::Label("abc".to_owned()))]))],
-            parse(vec![Token::OpenParen,
+            vec![Value::list(vec![Rc::new(Value::Label("abc".to_owned()))]),
+                 Value::list(vec![Rc::new(Value::Label("abc".to_owned()))])],
+            parse_tokens(vec![Token::OpenParen,
                        Token::Value(Value::Label("abc".to_owned())),
                        Token::CloseParen,
                        Token::OpenParen,
@@ -117,14 +196,14 @@ mod tests {
     fn test_parse_4() {
         assert_eq!(
             vec![
-                Rc::new(Value::List(vec![
+                Value::list(vec![
                     Rc::new(Value::Number(13.0)),
-                    Rc::new(Value::List(vec![
-                        Rc::new(Value::Number(13.0))])),
-                    Rc::new(Value::Number(13.0))])),
+                    Value::list(vec![
+                        Rc::new(Value::Number(13.0))]),
+                    Rc::new(Value::Number(13.0))]),
                 Rc::new(Value::Label("abc".to_string())),
             ],
-            parse(vec![
+            parse_tokens(vec![
                 Token::OpenParen,
                 Token::Value(Value::Number(13.0)),
                 Token::OpenParen,
@@ -140,15 +219,15 @@ mod tests {
         assert_eq!(
             vec![
                 Rc::new(Value::Quote(
-                    Rc::new(Value::List(vec![
+                    Value::list(vec![
                         Rc::new(Value::Number(13.0)),
-                        Rc::new(Value::List(vec![
-                            Rc::new(Value::Number(13.0))])),
-                        Rc::new(Value::Number(13.0))])))),
+                        Value::list(vec![
+                            Rc::new(Value::Number(13.0))]),
+                        Rc::new(Value::Number(13.0))]))),
                 Rc::new(Value::Quote(
                     Rc::new(Value::Label("abc".to_string())))),
             ],
-            parse(vec![
+            parse_tokens(vec![
                 Token::Quote,
                 Token::OpenParen,
                 Token::Value(Value::Number(13.0)),
@@ -163,37 +242,37 @@ mod tests {
 
     #[test]
     fn test_parse_6() {
-        use lex::lex;
+        use lex::Lexer;
         assert_eq!(vec![
-            Rc::new(Value::List(vec![
+            Value::list(vec![
                 Rc::new(Value::Label("-".to_string())),
-                Rc::new(Value::List(vec![
+                Value::list(vec![
                     Rc::new(Value::Label("/".to_string())),
-                    Rc::new(Value::Number(30.0)),
-                    Rc::new(Value::Number(2.0)),
-                    Rc::new(Value::Number(3.0))])),
-                Rc::new(Value::Number(-8.0))]))],
-            parse(lex("(- (/ 30 2 3) -8)".chars().fuse()).unwrap()).unwrap());
+                    Rc::new(Value::Integer(30)),
+                    Rc::new(Value::Integer(2)),
+                    Rc::new(Value::Integer(3))]),
+                Rc::new(Value::Integer(-8))])],
+            parse(Lexer::new("(- (/ 30 2 3) -8)".chars(), None), &mut SourceMap::new()).unwrap());
     }
 
     #[test]
     fn test_parse_panic_1() {
-        assert!(parse(vec![Token::CloseParen]).is_err());
+        assert!(parse_tokens(vec![Token::CloseParen]).is_err());
     }
 
     #[test]
     fn test_parse_panic_2() {
-        assert!(parse(vec![Token::OpenParen]).is_err());
+        assert!(parse_tokens(vec![Token::OpenParen]).is_err());
     }
 
     #[test]
     fn test_parse_panic_3() {
-        assert!(parse(vec![Token::OpenParen, Token::OpenParen, Token::CloseParen]).is_err());
+        assert!(parse_tokens(vec![Token::OpenParen, Token::OpenParen, Token::CloseParen]).is_err());
     }
 
     #[test]
     fn test_parse_panic_4() {
-        assert!(parse(vec![Token::OpenParen,
+        assert!(parse_tokens(vec![Token::OpenParen,
                            Token::Value(Value::Number(13.0)),
                            Token::OpenParen,
                            Token::Value(Value::Number(13.0)),
@@ -203,15 +282,75 @@ mod tests {
 
     #[test]
     fn test_parse_panic_5() {
-        assert!(parse(vec![Token::Value(Value::Number(13.0)),
+        assert!(parse_tokens(vec![Token::Value(Value::Number(13.0)),
                            Token::Quote]).is_err());
     }
 
     #[test]
     fn test_parse_panic_6() {
-        assert!(parse(vec![Token::OpenParen,
+        assert!(parse_tokens(vec![Token::OpenParen,
                            Token::Value(Value::Number(13.0)),
                            Token::Quote,
                            Token::CloseParen]).is_err());
     }
+
+    #[test]
+    fn test_parse_locations() {
+        use lex::Lexer;
+        let mut map = SourceMap::new();
+        let exprs = parse(Lexer::new("(a\n (b c))".chars(), None), &mut map).unwrap();
+        let loc = |line, column| Some(Location { file: None, line, column });
+        assert_eq!(loc(1, 1), map.get(&exprs[0]).cloned());
+        let outer = exprs[0].unwrap_list();
+        assert_eq!(loc(1, 2), map.get(&outer[0]).cloned());
+        assert_eq!(loc(2, 2), map.get(&outer[1]).cloned());
+        assert_eq!(loc(2, 5), map.get(&outer[1].unwrap_list()[1]).cloned());
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        use lex::Lexer;
+        let e = parse(Lexer::new("()\n  (a (b)".chars(), None), &mut SourceMap::new()).unwrap_err();
+        assert_eq!(Some(Location { file: None, line: 2, column: 3 }), e.location);
+        let e = parse(Lexer::new("(a))".chars(), None), &mut SourceMap::new()).unwrap_err();
+        assert_eq!(Some(Location { file: None, line: 1, column: 4 }), e.location);
+    }
+
+    #[test]
+    fn test_parse_datum_comment() {
+        use lex::Lexer;
+        let parse_str = |s: &str| parse(Lexer::new(s.chars(), None), &mut SourceMap::new());
+        assert_eq!(
+            vec![Value::list(vec![Rc::new(Value::Label("a".to_owned())),
+                                          Rc::new(Value::Label("c".to_owned()))])],
+            parse_str("(a #;(b 1 2) c #;d)").unwrap());
+        assert_eq!(
+            vec![Rc::new(Value::Label("c".to_owned()))],
+            parse_str("#; #; a b c").unwrap());
+        assert_eq!(
+            vec![Rc::new(Value::Quote(Rc::new(Value::Label("b".to_owned()))))],
+            parse_str("'#;a b").unwrap());
+        assert_eq!(Vec::<Rc<Value>>::new(), parse_str("#;a").unwrap());
+        assert!(parse_str("#;").is_err());
+        assert!(parse_str("(a #;)").is_err());
+        assert!(parse_str("(a #;b").is_err());
+    }
+
+    #[test]
+    fn test_parse_dotted() {
+        use lex::Lexer;
+        let parse_str = |s: &str| parse(Lexer::new(s.chars(), None), &mut SourceMap::new());
+        let label = |s: &str| Rc::new(Value::Label(s.to_owned()));
+        assert_eq!(vec![Value::cons(label("a"), label("b"))], parse_str("(a . b)").unwrap());
+        assert_eq!(vec![Value::cons(label("a"), Value::cons(label("b"), label("c")))],
+                   parse_str("(a b . #;x c #;y)").unwrap());
+        assert_eq!(vec![Value::cons(label("a"), Value::list(vec![label("b")]))],
+                   parse_str("(a . (b))").unwrap());
+        assert_eq!(ErrorKind::Parse, parse_str("(. a)").unwrap_err().kind);
+        assert_eq!(ErrorKind::Parse, parse_str("(a .)").unwrap_err().kind);
+        assert_eq!(ErrorKind::Parse, parse_str("(a . b c)").unwrap_err().kind);
+        assert_eq!(ErrorKind::Parse, parse_str(". a").unwrap_err().kind);
+        assert_eq!(ErrorKind::Incomplete, parse_str("(a .").unwrap_err().kind);
+        assert_eq!(ErrorKind::Incomplete, parse_str("(a . b").unwrap_err().kind);
+    }
 }
@@ -0,0 +1,251 @@
+use error::{Error, ErrorKind, Location, Result};
+use lex::Lexer;
+use parse::parse_one;
+use source_map::SourceMap;
+use std::cell::RefCell;
+use std::io::{self, BufReader, Read};
+use std::rc::Rc;
+use std::str;
+use types::Value;
+
+/// Reads values one top-level datum at a time from a stream of characters.
+/// Only as much input as the next datum needs is consumed, so a caller can
+/// act on each value before the rest of the input is available.
+///
+/// If the input ends partway through a datum, `read` fails with
+/// `ErrorKind::Incomplete` rather than `Lex` or `Parse`, so an interactive
+/// caller can ask for more input instead of reporting an error.
+pub struct Reader<I: Iterator<Item = char>> {
+    tokens: Lexer<I>,
+    source_map: SourceMap,
+    /// Where `Utf8Chars` leaves the error that ended its characters.
+    input_error: Rc<RefCell<Option<Error>>>,
+}
+
+impl<I: Iterator<Item = char>> Reader<I> {
+    /// `file` names the source in the locations of values read.
+    pub fn new(chars: I, file: Option<Rc<str>>) -> Self {
+        Reader {
+            tokens: Lexer::new(chars, file),
+            source_map: SourceMap::new(),
+            input_error: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Reads the next datum, or returns `None` at the end of the input.
+    pub fn read(&mut self) -> Result<Option<Rc<Value>>> {
+        self.source_map.prune();
+        let value = parse_one(&mut self.tokens, &mut self.source_map);
+        self.check_input(value)
+    }
+
+    /// Like `read`, but records locations in `map` instead.
+    pub(crate) fn read_into(&mut self, map: &mut SourceMap) -> Result<Option<Rc<Value>>> {
+        let value = parse_one(&mut self.tokens, map);
+        self.check_input(value)
+    }
+
+    /// Replaces the result of parsing with the error that cut the input
+    /// short, if there was one, since the datum may be truncated.
+    fn check_input(&self, value: Result<Option<Rc<Value>>>) -> Result<Option<Rc<Value>>> {
+        match self.input_error.borrow_mut().take() {
+            Some(e) => Err(e),
+            None => value,
+        }
+    }
+
+    /// Gets where `value`, or a value nested in it, was read from.
+    pub fn location(&self, value: &Rc<Value>) -> Option<Location> {
+        self.source_map.get(value).cloned()
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for Reader<I> {
+    type Item = Result<Rc<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+impl<R: Read> Reader<Utf8Chars<R>> {
+    /// Reads the UTF-8 text from `reader` as it is needed, so each datum
+    /// can be used before the rest of the input arrives.  Failing to read
+    /// or decode the input is reported as `ErrorKind::Io`.
+    pub fn from_read(reader: R, file: Option<Rc<str>>) -> Self {
+        let chars = Utf8Chars::new(reader, file.clone());
+        let input_error = chars.error.clone();
+        Reader { input_error, ..Reader::new(chars, file) }
+    }
+}
+
+/// Decodes UTF-8 from a `Read` one character at a time.  The first error
+/// ends the characters and is left for the `Reader` to report, at the
+/// position the next character would have had.
+pub struct Utf8Chars<R: Read> {
+    bytes: io::Bytes<BufReader<R>>,
+    error: Rc<RefCell<Option<Error>>>,
+    done: bool,
+    file: Option<Rc<str>>,
+    line: usize,
+    column: usize,
+}
+
+impl<R: Read> Utf8Chars<R> {
+    fn new(reader: R, file: Option<Rc<str>>) -> Self {
+        Utf8Chars {
+            bytes: BufReader::new(reader).bytes(),
+            error: Rc::new(RefCell::new(None)),
+            done: false,
+            file,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn invalid(&self) -> Error {
+        Error::new(ErrorKind::Io, "Source is not valid UTF-8")
+    }
+
+    fn decode(&mut self) -> Result<Option<char>> {
+        let first = match self.bytes.next() {
+            Some(b) => b?,
+            None => return Ok(None),
+        };
+        let width = match first {
+            0x00..=0x7f => 1,
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf4 => 4,
+            _ => return Err(self.invalid()),
+        };
+        let mut buf = [first, 0, 0, 0];
+        for b in &mut buf[1..width] {
+            *b = match self.bytes.next() {
+                Some(b) => b?,
+                None => return Err(self.invalid()),
+            };
+        }
+        match str::from_utf8(&buf[..width]) {
+            Ok(s) => Ok(s.chars().next()),
+            Err(_) => Err(self.invalid()),
+        }
+    }
+}
+
+impl<R: Read> Iterator for Utf8Chars<R> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.done {
+            return None;
+        }
+        match self.decode() {
+            Ok(Some(c)) => {
+                if c == '\n' {
+                    self.line += 1;
+                    self.column = 1;
+                } else {
+                    self.column += 1;
+                }
+                Some(c)
+            },
+            Ok(None) => {
+                self.done = true;
+                None
+            },
+            Err(e) => {
+                self.done = true;
+                let location = Location { file: self.file.clone(), line: self.line, column: self.column };
+                *self.error.borrow_mut() = Some(e.at(location));
+                None
+            },
+        }
+    }
+}
+
+/// Reads every datum in `s` without evaluating anything.
+pub fn read_str(s: &str) -> Result<Vec<Rc<Value>>> {
+    Reader::new(s.chars(), None).collect()
+}
+
+/// Reads every datum in the UTF-8 text from `reader` without evaluating
+/// anything.
+pub fn read_all<R: Read>(reader: R) -> Result<Vec<Rc<Value>>> {
+    Reader::from_read(reader, None).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::ErrorKind;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_reader_one_at_a_time() {
+        // Count how many characters have been pulled from the source.
+        let consumed = Cell::new(0);
+        let source = "(a b) c\n'd";
+        let mut reader = Reader::new(source.chars().inspect(|_| consumed.set(consumed.get() + 1)), None);
+        assert_eq!("(a b)", reader.read().unwrap().unwrap().to_string());
+        assert_eq!(5, consumed.get());
+        let c = reader.read().unwrap().unwrap();
+        assert_eq!("c", c.to_string());
+        assert_eq!(Some(Location { file: None, line: 1, column: 7 }), reader.location(&c));
+        assert_eq!("'d", reader.read().unwrap().unwrap().to_string());
+        assert_eq!(None, reader.read().unwrap());
+    }
+
+    #[test]
+    fn test_reader_iterator() {
+        let values: Result<Vec<_>> = Reader::new("1 #;2 3 ; 4".chars(), None).collect();
+        assert_eq!(vec![Rc::new(Value::Integer(1)), Rc::new(Value::Integer(3))], values.unwrap());
+    }
+
+    #[test]
+    fn test_reader_incomplete() {
+        for s in &["(a", "(a (b)", "\"abc", "\"a\\", "\"a\\u", "\"\\x4", "\"a \\  ", "#| a", "'", "`(a ,", "#;", "(a #;", "#\\"] {
+            let e = Reader::new(s.chars(), None).read().unwrap_err();
+            assert_eq!(ErrorKind::Incomplete, e.kind, "{}", s);
+        }
+        for s in &[")", "(')", "\"\\q\"", "#x1Z", "13`b"] {
+            let e = Reader::new(s.chars(), None).read().unwrap_err();
+            assert!(e.kind == ErrorKind::Lex || e.kind == ErrorKind::Parse, "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_read_str() {
+        let values = read_str("(name \"scib\") (version 1/2) ; done").unwrap();
+        assert_eq!(2, values.len());
+        let fields = values[1].as_list().unwrap();
+        assert_eq!("version", fields[0].as_label().unwrap());
+        assert_eq!(0.5, fields[1].as_number().unwrap());
+        assert_eq!(ErrorKind::Incomplete, read_str("(a").unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_read_all() {
+        let values = read_all("port 8080 host \"λ\"".as_bytes()).unwrap();
+        assert_eq!(8080, values[1].as_integer().unwrap());
+        assert_eq!("λ", values[3].as_str().unwrap());
+        assert_eq!(ErrorKind::Io, read_all(&b"\xff"[..]).unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_reader_from_read() {
+        let mut reader = Reader::from_read(&b"(a \xce\xbb)\n\"b\xe2\x82"[..], Some(Rc::from("in")));
+        assert_eq!("(a λ)", reader.read().unwrap().unwrap().to_string());
+        let e = reader.read().unwrap_err();
+        assert_eq!(ErrorKind::Io, e.kind);
+        assert_eq!(Some(Location { file: Some(Rc::from("in")), line: 2, column: 3 }), e.location);
+        assert_eq!(None, reader.read().unwrap());
+    }
+
+    #[test]
+    fn test_reader_after_complete_datum() {
+        let mut reader = Reader::new("(a) (b".chars(), None);
+        assert_eq!("(a)", reader.read().unwrap().unwrap().to_string());
+        assert_eq!(ErrorKind::Incomplete, reader.read().unwrap_err().kind);
+    }
+}
@@ -0,0 +1,351 @@
+//! Converts Rust data into `Value`s with serde.
+//!
+//! Sequences and tuples become lists, structs become keyword plists such as
+//! `(:name "scib" :version 1)`, and maps become association lists such as
+//! `(("a" 1) ("b" 2))`.  A unit enum variant is the label of its name, and
+//! any other variant is a list starting with that label.  `None`, `()` and
+//! `false` are all `nil`.
+
+use error::{Error, ErrorKind, Result};
+use numeric::Number;
+use serde::ser::{self, Serialize};
+use std::fmt::Display;
+use std::rc::Rc;
+use types::Value;
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new(ErrorKind::Type, msg.to_string())
+    }
+}
+
+/// Converts `value` into a `Value`.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Rc<Value>> {
+    value.serialize(Serializer)
+}
+
+/// A serde `Serializer` that produces a `Value`.
+pub struct Serializer;
+
+fn big(s: String) -> Rc<Value> {
+    Number::from_bigint(s.parse().unwrap()).into_value()
+}
+
+fn label(name: &str) -> Rc<Value> {
+    Rc::new(Value::Label(name.to_string()))
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Rc<Value>;
+    type Error = Error;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeAlist;
+    type SerializeStruct = SerializeList;
+    type SerializeStructVariant = SerializeList;
+
+    fn serialize_bool(self, v: bool) -> Result<Rc<Value>> {
+        Ok(Rc::new(if v { Value::True } else { Value::Nil }))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Rc<Value>> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Rc<Value>> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Rc<Value>> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Rc<Value>> {
+        Ok(Rc::new(Value::Integer(v)))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Rc<Value>> {
+        Ok(big(v.to_string()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Rc<Value>> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Rc<Value>> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Rc<Value>> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Rc<Value>> {
+        Ok(big(v.to_string()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Rc<Value>> {
+        Ok(big(v.to_string()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Rc<Value>> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Rc<Value>> {
+        Ok(Rc::new(Value::Number(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Rc<Value>> {
+        Ok(Rc::new(Value::Char(v)))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Rc<Value>> {
+        Ok(Rc::new(Value::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Rc<Value>> {
+        Ok(Value::list(v.iter().map(|&b| Rc::new(Value::Integer(b as i64)))))
+    }
+
+    fn serialize_none(self) -> Result<Rc<Value>> {
+        Ok(Rc::new(Value::Nil))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Rc<Value>> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Rc<Value>> {
+        Ok(Rc::new(Value::Nil))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Rc<Value>> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Rc<Value>> {
+        Ok(label(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Rc<Value>> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str,
+                                                        value: &T) -> Result<Rc<Value>> {
+        Ok(Value::list(vec![label(variant), value.serialize(self)?]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList> {
+        Ok(SerializeList(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str,
+                               _len: usize) -> Result<SerializeList> {
+        Ok(SerializeList(vec![label(variant)]))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeAlist> {
+        Ok(SerializeAlist { entries: Vec::with_capacity(len.unwrap_or(0)), key: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len * 2))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str,
+                                _len: usize) -> Result<SerializeList> {
+        Ok(SerializeList(vec![label(variant)]))
+    }
+}
+
+/// Builds a list, used for sequences, tuples, and the plists of structs.
+pub struct SerializeList(Vec<Rc<Value>>);
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.0.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn push_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+        self.0.push(Rc::new(Value::Label(format!(":{}", key))));
+        self.push(value)
+    }
+
+    fn finish(self) -> Result<Rc<Value>> {
+        Ok(Value::list(self.0))
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Rc<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Rc<Value>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Rc<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Rc<Value>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Rc<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Rc<Value>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Rc<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Rc<Value>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeList {
+    type Ok = Rc<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+        self.push_field(key, value)
+    }
+
+    fn end(self) -> Result<Rc<Value>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeList {
+    type Ok = Rc<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+        self.push_field(key, value)
+    }
+
+    fn end(self) -> Result<Rc<Value>> {
+        self.finish()
+    }
+}
+
+/// Builds an association list of `(key value)` entries for a map.
+pub struct SerializeAlist {
+    entries: Vec<Rc<Value>>,
+    key: Option<Rc<Value>>,
+}
+
+impl ser::SerializeMap for SerializeAlist {
+    type Ok = Rc<Value>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().expect("serialize_value called before serialize_key");
+        self.entries.push(Value::list(vec![key, value.serialize(Serializer)?]));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Rc<Value>> {
+        Ok(Value::list(self.entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    struct Config {
+        name: String,
+        port: u16,
+        debug: bool,
+        tags: Vec<String>,
+        mode: Mode,
+    }
+
+    #[derive(Serialize)]
+    enum Mode {
+        Fast,
+        Limit(u32),
+        Range(i32, i32),
+        Named { level: u8 },
+    }
+
+    #[test]
+    fn test_to_value_struct() {
+        let config = Config {
+            name: "scib".to_string(),
+            port: 8080,
+            debug: false,
+            tags: vec!["a".to_string(), "b".to_string()],
+            mode: Mode::Fast,
+        };
+        assert_eq!("(:name \"scib\" :port 8080 :debug nil :tags (\"a\" \"b\") :mode Fast)",
+                   to_value(&config).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_to_value_variants() {
+        assert_eq!("(Limit 3)", to_value(&Mode::Limit(3)).unwrap().to_string());
+        assert_eq!("(Range -1 1)", to_value(&Mode::Range(-1, 1)).unwrap().to_string());
+        assert_eq!("(Named :level 2)", to_value(&Mode::Named { level: 2 }).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_to_value_primitives() {
+        assert_eq!("t", to_value(&true).unwrap().to_string());
+        assert_eq!("nil", to_value(&None::<i32>).unwrap().to_string());
+        assert_eq!("2.5", to_value(&2.5).unwrap().to_string());
+        assert_eq!("#\\a", to_value(&'a').unwrap().to_string());
+        assert_eq!("18446744073709551615", to_value(&u64::MAX).unwrap().to_string());
+        assert_eq!("(1 \"x\")", to_value(&(1, "x")).unwrap().to_string());
+        let mut map = BTreeMap::new();
+        map.insert("a", 1);
+        map.insert("b", 2);
+        assert_eq!("((\"a\" 1) (\"b\" 2))", to_value(&map).unwrap().to_string());
+    }
+}
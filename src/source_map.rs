@@ -0,0 +1,32 @@
+use error::Location;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use types::Value;
+
+/// Records where parsed values came from without storing anything in
+/// `Value` itself.  Entries are keyed by the address of the node and hold
+/// a `Weak` to it, which keeps the allocation (and so the address) from
+/// being reused while the entry exists.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    locations: HashMap<*const Value, (Weak<Value>, Location)>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap { locations: HashMap::new() }
+    }
+
+    pub fn insert(&mut self, value: &Rc<Value>, location: Location) {
+        self.locations.insert(&**value as *const Value, (Rc::downgrade(value), location));
+    }
+
+    pub fn get(&self, value: &Rc<Value>) -> Option<&Location> {
+        self.locations.get(&(&**value as *const Value)).map(|(_, l)| l)
+    }
+
+    /// Forgets the locations of values that have been dropped.
+    pub fn prune(&mut self) {
+        self.locations.retain(|_, (w, _)| w.upgrade().is_some());
+    }
+}
@@ -1,16 +1,29 @@
 use std::rc::Rc;
-use std::io::{Result, Error, ErrorKind};
+use bignum::{BigInt, Rational};
+use builtins::is_equal;
+use error::{Result, Error, ErrorKind};
 use instance::Scib;
+use lex::CHAR_NAMES;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt;
 
 #[derive(Debug, PartialEq)]
 pub enum Value {
     Nil,
     True,
+    Integer(i64),
+    /// An integer too large for `Integer`.
+    BigInt(BigInt),
+    /// An exact fraction that isn't an integer.
+    Rational(Rational),
     Number(f64),
+    Char(char),
     String(String),
     Label(String),
-    List(Vec<Rc<Value>>),
+    /// A cons cell.  Lists are chains of pairs ending in `Nil`, and dotted
+    /// syntax such as `(a . b)` ends the chain with another value instead.
+    Pair(Pair),
     Quote(Rc<Value>),
     Backquote(Rc<Value>),
     Unquote(Rc<Value>),
@@ -23,43 +36,302 @@ impl Value {
     pub fn as_label(&self) -> Result<&String> {
         match *self {
             Value::Label(ref l) => Ok(l),
-            _ => Err(Error::new(ErrorKind::InvalidInput,
+            _ => Err(Error::new(ErrorKind::Type,
                                 format!("Expected label, found '{:?}'", self))),
         }
     }
 
-    pub fn as_list(&self) -> Result<&Vec<Rc<Value>>> {
+    /// Gets an exact integer that fits in an `i64`.
+    pub fn as_integer(&self) -> Result<i64> {
         match *self {
-            Value::List(ref l) => Ok(l),
-            _ => Err(Error::new(ErrorKind::InvalidInput,
-                                format!("Expected list, found '{:?}'", self))),
+            Value::Integer(i) => Ok(i),
+            _ => Err(Error::new(ErrorKind::Type,
+                                format!("Expected integer, found '{:?}'", self))),
         }
     }
 
-    pub fn unwrap_list(&self) -> &Vec<Rc<Value>> {
+    /// Gets any number, converting exact numbers to the nearest `f64`.
+    pub fn as_number(&self) -> Result<f64> {
         match *self {
-            Value::List(ref l) => l,
-            _ => unreachable!(),
+            Value::Integer(i) => Ok(i as f64),
+            Value::BigInt(ref i) => Ok(i.to_f64()),
+            Value::Rational(ref r) => Ok(r.to_f64()),
+            Value::Number(n) => Ok(n),
+            _ => Err(Error::new(ErrorKind::Type,
+                                format!("Expected number, found '{:?}'", self))),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str> {
+        match *self {
+            Value::String(ref s) => Ok(s),
+            _ => Err(Error::new(ErrorKind::Type,
+                                format!("Expected string, found '{:?}'", self))),
+        }
+    }
+
+    /// Gets `t` as `true` and `nil` as `false`.
+    pub fn as_bool(&self) -> Result<bool> {
+        match *self {
+            Value::True => Ok(true),
+            Value::Nil => Ok(false),
+            _ => Err(Error::new(ErrorKind::Type,
+                                format!("Expected t or nil, found '{:?}'", self))),
+        }
+    }
+
+    pub fn as_char(&self) -> Result<char> {
+        match *self {
+            Value::Char(c) => Ok(c),
+            _ => Err(Error::new(ErrorKind::Type,
+                                format!("Expected character, found '{:?}'", self))),
+        }
+    }
+
+    /// Gets the elements of a proper list.
+    pub fn as_list(&self) -> Result<Vec<Rc<Value>>> {
+        self.list_elements().ok_or_else(|| Error::new(ErrorKind::Type,
+                                                      format!("Expected list, found '{:?}'", self)))
+    }
+
+    pub fn unwrap_list(&self) -> Vec<Rc<Value>> {
+        self.list_elements().unwrap()
+    }
+
+    pub fn cons(car: Rc<Value>, cdr: Rc<Value>) -> Rc<Value> {
+        Rc::new(Value::Pair(Pair::new(car, cdr)))
+    }
+
+    /// Builds a proper list of `elements` out of pairs, or `nil` if there
+    /// are none.
+    pub fn list<I>(elements: I) -> Rc<Value>
+        where I: IntoIterator<Item = Rc<Value>>, I::IntoIter: DoubleEndedIterator {
+        elements.into_iter().rev().fold(Rc::new(Value::Nil), |cdr, car| Value::cons(car, cdr))
+    }
+
+    /// Gets the elements of a proper list, which is `nil` or a chain of
+    /// pairs ending in `nil`.  Returns `None` for improper and circular
+    /// lists and anything that isn't a list.
+    pub fn list_elements(&self) -> Option<Vec<Rc<Value>>> {
+        let mut elements = Vec::new();
+        let mut tail = match *self {
+            Value::Nil => return Some(elements),
+            Value::Pair(ref p) => {
+                elements.push(p.car());
+                p.cdr()
+            },
+            _ => return None,
+        };
+        // Follows the chain at half speed, so `tail` catches up with it
+        // only if the chain loops.
+        let mut slow = tail.clone();
+        let mut advance_slow = false;
+        loop {
+            tail = match *tail {
+                Value::Nil => return Some(elements),
+                Value::Pair(ref p) => {
+                    elements.push(p.car());
+                    p.cdr()
+                },
+                _ => return None,
+            };
+            if advance_slow {
+                slow = match *slow {
+                    Value::Pair(ref p) => p.cdr(),
+                    _ => unreachable!(),
+                };
+            }
+            advance_slow = !advance_slow;
+            if Rc::ptr_eq(&slow, &tail) {
+                return None;
+            }
+        }
+    }
+}
+
+/// The two halves of a cons cell.  Either can be replaced in place by
+/// `set-car!` or `set-cdr!`, which every list sharing the cell will see.
+pub struct Pair {
+    pub car: RefCell<Rc<Value>>,
+    pub cdr: RefCell<Rc<Value>>,
+}
+
+impl Pair {
+    pub fn new(car: Rc<Value>, cdr: Rc<Value>) -> Self {
+        Pair { car: RefCell::new(car), cdr: RefCell::new(cdr) }
+    }
+
+    pub fn car(&self) -> Rc<Value> {
+        self.car.borrow().clone()
+    }
+
+    pub fn cdr(&self) -> Rc<Value> {
+        self.cdr.borrow().clone()
+    }
+
+    /// Writes the list starting with `self`, or `...` if it is already
+    /// open around it.  See `Value::write`.
+    fn write(&self, f: &mut fmt::Formatter, open: &mut Vec<*const Pair>) -> fmt::Result {
+        if open.contains(&(self as *const Pair)) {
+            return write!(f, "...");
+        }
+        let depth = open.len();
+        open.push(self);
+        write!(f, "(")?;
+        self.car.borrow().write(f, open)?;
+        let mut tail = self.cdr();
+        loop {
+            tail = match *tail {
+                Value::Nil => break,
+                Value::Pair(ref p) if open.contains(&(p as *const Pair)) => {
+                    write!(f, " ...")?;
+                    break;
+                },
+                Value::Pair(ref p) => {
+                    open.push(p);
+                    write!(f, " ")?;
+                    p.car.borrow().write(f, open)?;
+                    p.cdr()
+                },
+                ref v => {
+                    write!(f, " . ")?;
+                    v.write(f, open)?;
+                    break;
+                },
+            };
+        }
+        open.truncate(depth);
+        write!(f, ")")
+    }
+}
+
+/// Written like `Display`, since the derived form would never end for a
+/// circular list.
+impl fmt::Debug for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &mut Vec::new())
+    }
+}
+
+/// Compared like `equal`, since the derived comparison would never end for
+/// circular lists.
+impl PartialEq for Pair {
+    fn eq(&self, other: &Pair) -> bool {
+        is_equal(&self.car(), &other.car()) && is_equal(&self.cdr(), &other.cdr())
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &mut Vec::new())
+    }
+}
+
+impl Value {
+    /// Writes `self` for `Display`.  `open` holds the pairs being written
+    /// around it, and one met again is written as `...` so that a circular
+    /// list made with `set-car!` or `set-cdr!` still ends.
+    fn write(&self, f: &mut fmt::Formatter, open: &mut Vec<*const Pair>) -> fmt::Result {
+        match *self {
+            Value::Nil => write!(f, "nil"),
+            Value::True => write!(f, "t"),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::BigInt(ref i) => write!(f, "{}", i),
+            Value::Rational(ref r) => write!(f, "{}", r),
+            // Keep a decimal point on whole numbers so they read back as
+            // floats.
+            Value::Number(n) if n.is_finite() && n == n.trunc() => write!(f, "{:.1}", n),
+            Value::Number(n) if n.is_nan() => write!(f, "+nan.0"),
+            Value::Number(n) if n.is_infinite() => write!(f, "{}inf.0", if n > 0.0 { "+" } else { "-" }),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Char(c) => match CHAR_NAMES.iter().find(|&&(_, named)| named == c) {
+                Some(&(name, _)) => write!(f, "#\\{}", name),
+                None if c.is_control() || c.is_whitespace() => write!(f, "#\\x{:x}", c as u32),
+                None => write!(f, "#\\{}", c),
+            },
+            Value::String(ref s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '\\' => write!(f, "\\\\")?,
+                        '"' => write!(f, "\\\"")?,
+                        '\t' => write!(f, "\\t")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\0' => write!(f, "\\0")?,
+                        c if c.is_control() => write!(f, "\\x{:x};", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            },
+            Value::Label(ref l) => write!(f, "{}", l),
+            Value::Pair(ref p) => p.write(f, open),
+            Value::Quote(ref v) => {
+                write!(f, "'")?;
+                v.write(f, open)
+            },
+            Value::Backquote(ref v) => {
+                write!(f, "`")?;
+                v.write(f, open)
+            },
+            Value::Unquote(ref v) => {
+                write!(f, ",")?;
+                v.write(f, open)
+            },
+            Value::UnquoteList(ref v) => {
+                write!(f, ",@")?;
+                v.write(f, open)
+            },
+            Value::Function(ref func) => write!(f, "#<function {}>", func.params),
+            Value::Macro(ref m) => write!(f, "#<macro {}>", m.params),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
 pub struct Function {
     pub params: Parameters,
     pub body: Body,
+    /// The environment the function was created in, or `None` if it was
+    /// created at the top level.
+    pub env: Option<Rc<Environment>>,
 }
 pub type Macro = Function;
 
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        self.params == other.params && self.body == other.body &&
+            match (&self.env, &other.env) {
+                (Some(e1), Some(e2)) => Rc::ptr_eq(e1, e2),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+// The captured environment may contain the function itself, so only
+// print where it lives rather than its contents.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("params", &self.params)
+            .field("body", &self.body)
+            .field("env", &self.env.as_ref().map(|env| &**env as *const Environment))
+            .finish()
+    }
+}
+
 pub enum Body {
     Lisp(Vec<Rc<Value>>),
-    Rust(fn(&mut Scib) -> Result<Rc<Value>>),
+    Rust(Box<RustFn>),
 }
 
+pub type RustFn = dyn Fn(&mut Scib, &[Rc<Value>]) -> Result<Rc<Value>>;
+
 impl PartialEq for Body {
     fn eq(&self, other: &Body) -> bool {
         match (self, other) {
-            (&Body::Lisp(ref l1), &Body::Lisp(ref l2)) => l1 == l2,
+            (Body::Lisp(l1), Body::Lisp(l2)) => l1 == l2,
             _ => false,
         }
     }
@@ -72,16 +344,16 @@ impl fmt::Debug for Body {
 }
 impl fmt::Display for Body {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            &Body::Lisp(ref body) => {
+        match *self {
+            Body::Lisp(ref body) => {
                 let mut first = true;
                 for v in body {
-                    try!(write!(f, "{}{:?}", if first { "" } else { " " }, v));
+                    write!(f, "{}{:?}", if first { "" } else { " " }, v)?;
                     first = false;
                 }
             },
-            &Body::Rust(_) => {
-                try!(write!(f, "..."));
+            Body::Rust(_) => {
+                write!(f, "...")?;
             },
         }
         Ok(())
@@ -97,41 +369,47 @@ pub struct Parameters {
 
 impl fmt::Display for Parameters {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        try!(write!(f, "("));
+        write!(f, "(")?;
         let mut first = true;
         for r in &self.required {
-            try!(write!(f, "{}{}", if first { "" } else { " " }, r));
+            write!(f, "{}{}", if first { "" } else { " " }, r)?;
             first = false;
         }
         if !self.optional.is_empty() {
             if first {
                 first = false;
             } else {
-                try!(write!(f, " "));
+                write!(f, " ")?;
             }
-            try!(write!(f, "&optional"));
+            write!(f, "&optional")?;
             for o in &self.optional {
-                try!(write!(f, " {}", o));
+                write!(f, " {}", o)?;
             }
         }
-        if self.rest.is_some() {
-            if first {
-                first = false;
-            } else {
-                try!(write!(f, " "));
+        if let Some(ref rest) = self.rest {
+            if !first {
+                write!(f, " ")?;
             }
-            try!(write!(f, "&rest {}", self.rest.as_ref().unwrap()));
+            write!(f, "&rest {}", rest)?;
         }
-        try!(write!(f, ")"));
+        write!(f, ")")?;
         Ok(())
     }
 }
 
 impl Parameters {
+    pub fn new(required: &[&str], optional: &[&str], rest: Option<&str>) -> Self {
+        Parameters {
+            required: required.iter().map(|s| String::from(*s)).collect(),
+            optional: optional.iter().map(|s| String::from(*s)).collect(),
+            rest: rest.map(String::from),
+        }
+    }
+
     pub fn check_params_len(&self, len: usize) -> Result<()> {
         let len = len - 1;
         if len < self.required.len() {
-            Err(Error::new(ErrorKind::InvalidInput,
+            Err(Error::new(ErrorKind::Arity,
                            format!("Not enough arguments to function call (requires {} {})",
                                    if self.optional.is_empty() && self.rest.is_none() {
                                        "exactly"
@@ -140,7 +418,7 @@ impl Parameters {
                                    },
                                    self.required.len())))
         } else if self.rest.is_none() && len > self.required.len() + self.optional.len() {
-            Err(Error::new(ErrorKind::InvalidInput,
+            Err(Error::new(ErrorKind::Arity,
                            format!("Too many arguments to function call (requires {} {})",
                                    if self.optional.is_empty() {
                                        "exactly"
@@ -166,9 +444,324 @@ impl Parameters {
             v.push((r.clone(), iter.next().unwrap_or_else(|| Rc::new(Value::Nil))));
         }
         match self.rest {
-            Some(ref rest) => v.push((rest.clone(), Rc::new(Value::List(iter.collect())))),
+            Some(ref rest) => v.push((rest.clone(), Value::list(iter.collect::<Vec<_>>()))),
             None => assert!(iter.next().is_none()),
         }
         v
     }
 }
+
+/// A frame of local bindings.  Lookups that miss in this frame continue in
+/// `parent`, and lookups that miss in every frame fall through to the
+/// globals held by `Scib`.
+#[derive(Debug, Default)]
+pub struct Environment {
+    bindings: RefCell<HashMap<String, Rc<Value>>>,
+    parent: Option<Rc<Environment>>,
+}
+
+impl Environment {
+    pub fn new(parent: Option<Rc<Environment>>) -> Self {
+        Environment {
+            bindings: RefCell::new(HashMap::new()),
+            parent,
+        }
+    }
+
+    pub fn parent(&self) -> Option<&Rc<Environment>> {
+        self.parent.as_ref()
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Rc<Value>> {
+        let mut env = self;
+        loop {
+            if let Some(v) = env.bindings.borrow().get(name) {
+                return Some(v.clone());
+            }
+            match env.parent {
+                Some(ref parent) => env = parent,
+                None => return None,
+            }
+        }
+    }
+
+    /// Binds `name` in this frame, shadowing any binding in a parent frame.
+    pub fn define(&self, name: String, value: Rc<Value>) -> Option<Rc<Value>> {
+        self.bindings.borrow_mut().insert(name, value)
+    }
+
+    /// Rebinds `name` in the innermost frame that binds it.  If no frame
+    /// binds `name`, `value` is handed back.
+    pub fn assign(&self, name: &str, value: Rc<Value>) -> ::std::result::Result<Rc<Value>, Rc<Value>> {
+        let mut env = self;
+        loop {
+            if let Some(v) = env.bindings.borrow_mut().get_mut(name) {
+                return Ok(::std::mem::replace(v, value));
+            }
+            match env.parent {
+                Some(ref parent) => env = parent,
+                None => return Err(value),
+            }
+        }
+    }
+
+    /// Removes `name` from this frame only.
+    pub fn unbind(&self, name: &str) -> Option<Rc<Value>> {
+        self.bindings.borrow_mut().remove(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lex::Lexer;
+    use parse::parse;
+    use source_map::SourceMap;
+
+    fn read(s: &str) -> Vec<Rc<Value>> {
+        parse(Lexer::new(s.chars(), None), &mut SourceMap::new()).unwrap()
+    }
+
+    #[test]
+    fn test_accessors() {
+        let values = read("1 2.5 1/4 \"s\" t nil #\\c (a) b");
+        assert_eq!(1, values[0].as_integer().unwrap());
+        assert_eq!(2.5, values[1].as_number().unwrap());
+        assert_eq!(0.25, values[2].as_number().unwrap());
+        assert_eq!("s", values[3].as_str().unwrap());
+        assert!(values[4].as_bool().unwrap());
+        assert!(!values[5].as_bool().unwrap());
+        assert_eq!('c', values[6].as_char().unwrap());
+        assert_eq!(1, values[7].as_list().unwrap().len());
+        assert_eq!("b", values[8].as_label().unwrap());
+        assert_eq!(ErrorKind::Type, values[1].as_integer().unwrap_err().kind);
+        assert_eq!(ErrorKind::Type, values[3].as_number().unwrap_err().kind);
+        assert_eq!(ErrorKind::Type, values[8].as_str().unwrap_err().kind);
+        assert_eq!(ErrorKind::Type, values[0].as_bool().unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_display_1() {
+        assert_eq!("(a 1 1.0 2.5 \"x\\\"y\\\\z\\n\" nil t nil)",
+                   read("(a 1 1. 2.5 \"x\\\"y\\\\z\\n\" nil t ())")[0].to_string());
+    }
+
+    #[test]
+    fn test_display_special_floats() {
+        assert_eq!("(+inf.0 -inf.0 +nan.0 1000.0)",
+                   read("(+inf.0 -inf.0 +nan.0 1e3)")[0].to_string());
+    }
+
+    #[test]
+    fn test_display_string_escapes() {
+        assert_eq!("\"\\r\\0\\x1b;\\x7f;λ\"",
+                   read("\"\\r\\0\\u{1b}\\x7F;\\u{3bb}\"")[0].to_string());
+    }
+
+    #[test]
+    fn test_display_char() {
+        assert_eq!("(#\\a #\\space #\\newline #\\A #\\( #\\x1 #\\λ)",
+                   read("(#\\a #\\space #\\x0a #\\x41 #\\( #\\x1 #\\λ)")[0].to_string());
+    }
+
+    #[test]
+    fn test_display_quotes() {
+        assert_eq!("'(a `(b ,c ,@d))",
+                   read("'(a `(b ,c ,@d))")[0].to_string());
+    }
+
+    #[test]
+    fn test_display_pair() {
+        let n = |i| Rc::new(Value::Integer(i));
+        assert_eq!("(1 . 2)", Value::cons(n(1), n(2)).to_string());
+        assert_eq!("(1 2 . 3)", Value::cons(n(1), Value::cons(n(2), n(3))).to_string());
+        assert_eq!("(1 2)", Value::cons(n(1), Value::cons(n(2), Rc::new(Value::Nil))).to_string());
+        assert_eq!("(1 2 3)", Value::cons(n(1), Value::list(vec![n(2), n(3)])).to_string());
+        assert_eq!("((1 . 2))", Value::cons(Value::cons(n(1), n(2)), Rc::new(Value::Nil)).to_string());
+    }
+
+    #[test]
+    fn test_display_circular() {
+        let n = |i| Rc::new(Value::Integer(i));
+        let tail = Value::cons(n(2), Rc::new(Value::Nil));
+        let list = Value::cons(n(1), tail.clone());
+        if let Value::Pair(ref p) = *tail {
+            *p.cdr.borrow_mut() = list.clone();
+        }
+        assert_eq!("(1 2 ...)", list.to_string());
+        if let Value::Pair(ref p) = *list {
+            *p.car.borrow_mut() = list.clone();
+        }
+        assert_eq!("(... 2 ...)", list.to_string());
+        // Sharing without a cycle is written out in full.
+        let shared = Value::cons(n(1), n(2));
+        assert_eq!("((1 . 2) (1 . 2))",
+                   Value::cons(shared.clone(), Value::cons(shared, Rc::new(Value::Nil))).to_string());
+    }
+
+    #[test]
+    fn test_list_elements() {
+        let n = |i| Rc::new(Value::Integer(i));
+        assert_eq!(Some(vec![]), Value::Nil.list_elements());
+        assert_eq!(Some(vec![n(1), n(2), n(3)]),
+                   Value::cons(n(1), Value::list(vec![n(2), n(3)])).list_elements());
+        assert_eq!(None, Value::cons(n(1), n(2)).list_elements());
+        assert_eq!(None, n(1).list_elements());
+        for len in 1..5 {
+            let last = Value::cons(n(len), Rc::new(Value::Nil));
+            let mut list = last.clone();
+            for i in (0..len).rev() {
+                list = Value::cons(n(i), list);
+            }
+            if let Value::Pair(ref p) = *last {
+                *p.cdr.borrow_mut() = list.clone();
+            }
+            assert_eq!(None, list.list_elements(), "{}", len);
+        }
+    }
+
+    #[test]
+    fn test_pair_eq_circular() {
+        let n = |i| Rc::new(Value::Integer(i));
+        let circle = |i| {
+            let list = Value::list(vec![n(1), n(i)]);
+            if let Value::Pair(ref p) = *list {
+                if let Value::Pair(ref q) = *p.cdr() {
+                    *q.cdr.borrow_mut() = list.clone();
+                }
+            }
+            list
+        };
+        assert_eq!(*circle(2), *circle(2));
+        assert_ne!(*circle(2), *circle(3));
+        assert_eq!(*Value::list(vec![n(1), n(2)]), *Value::cons(n(1), Value::cons(n(2), Rc::new(Value::Nil))));
+    }
+
+    #[test]
+    fn test_display_function() {
+        let mut instance = Scib::new();
+        assert_eq!("#<function (x &optional y &rest z)>",
+                   instance.eval("(lambda (x &optional y &rest z) x)").unwrap().to_string());
+        assert_eq!("#<macro (cond &rest rest)>",
+                   instance.eval("when").unwrap().to_string());
+        assert_eq!("#<function (&rest numbers)>",
+                   instance.eval("+").unwrap().to_string());
+    }
+
+    /// A xorshift generator, so the round trip test is reproducible without
+    /// pulling in a dependency.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    fn gen_label(rng: &mut Rng) -> String {
+        const FIRST: &str = "abcdefghijklmnopqrstuvwxyz_!?*<>=/";
+        const REST: &str = "abcdefghijklmnopqrstuvwxyz0123456789-+_!?*<>=/:.#";
+        loop {
+            let mut s = String::new();
+            s.push(FIRST.chars().nth(rng.below(FIRST.len() as u64) as usize).unwrap());
+            for _ in 0..rng.below(6) {
+                s.push(REST.chars().nth(rng.below(REST.len() as u64) as usize).unwrap());
+            }
+            // Skip labels the reader would turn into other values.
+            if s != "t" && s != "nil" && s.parse::<f64>().is_err() {
+                return s;
+            }
+        }
+    }
+
+    fn gen_string(rng: &mut Rng) -> String {
+        const CHARS: &[char] = &['a', 'Z', '0', ' ', '"', '\\', '\n', '\t', '\r', '\0', '\u{1b}', '(', ')', ';', '\'', 'λ', '✓'];
+        (0..rng.below(8)).map(|_| CHARS[rng.below(CHARS.len() as u64) as usize]).collect()
+    }
+
+    fn gen_number(rng: &mut Rng) -> f64 {
+        match rng.below(4) {
+            0 => rng.below(1000) as f64 - 500.0,
+            1 => (rng.below(100000) as f64 - 50000.0) / 64.0,
+            2 => f64::from_bits(rng.next()),
+            _ => [0.1, 1e300, -2.5e-300, f64::INFINITY, f64::NEG_INFINITY][rng.below(5) as usize],
+        }
+    }
+
+    fn gen_value(rng: &mut Rng, depth: u32, in_backquote: i32) -> Value {
+        let choices = if depth == 0 { 5 } else { 10 };
+        match rng.below(choices) {
+            0 => Value::Nil,
+            1 => Value::True,
+            2 if rng.below(2) == 0 => Value::Integer(rng.next() as i64 >> rng.below(64)),
+            2 if rng.below(2) == 0 => {
+                let digits: String = (0..1 + rng.below(40)).map(|_| (b'0' + rng.below(10) as u8) as char).collect();
+                let numer: BigInt = digits.parse().unwrap();
+                let numer = if rng.below(2) == 0 { -&numer } else { numer };
+                let denom = BigInt::from(1 + rng.below(1000) as i64);
+                let r = Rational::new(numer, denom);
+                if !r.is_integer() {
+                    Value::Rational(r)
+                } else if r.numer().to_i64().is_some() {
+                    Value::Integer(r.numer().to_i64().unwrap())
+                } else {
+                    Value::BigInt(r.numer().clone())
+                }
+            },
+            2 => loop {
+                let n = gen_number(rng);
+                if !n.is_nan() {
+                    break Value::Number(n);
+                }
+            },
+            3 if rng.below(4) == 0 => Value::Char(gen_string(rng).chars().next().unwrap_or('\u{7f}')),
+            3 => Value::String(gen_string(rng)),
+            4 => Value::Label(gen_label(rng)),
+            6 if rng.below(3) == 0 => {
+                let tail = loop {
+                    match gen_value(rng, 0, in_backquote) {
+                        Value::Nil => continue,
+                        v => break Rc::new(v),
+                    }
+                };
+                let cars: Vec<_> = (0..1 + rng.below(3))
+                    .map(|_| Rc::new(gen_value(rng, depth - 1, in_backquote)))
+                    .collect();
+                let pair = cars.into_iter().rev().fold(tail, |cdr, car| Value::cons(car, cdr));
+                Rc::try_unwrap(pair).unwrap()
+            },
+            5 | 6 => {
+                let elements: Vec<_> = (0..rng.below(5))
+                    .map(|_| Rc::new(gen_value(rng, depth - 1, in_backquote)))
+                    .collect();
+                Rc::try_unwrap(Value::list(elements)).unwrap()
+            },
+            7 => Value::Quote(Rc::new(gen_value(rng, depth - 1, in_backquote))),
+            8 => Value::Backquote(Rc::new(gen_value(rng, depth - 1, in_backquote + 1))),
+            _ if in_backquote > 0 => {
+                let v = Rc::new(gen_value(rng, depth - 1, in_backquote - 1));
+                if rng.below(2) == 0 { Value::Unquote(v) } else { Value::UnquoteList(v) }
+            },
+            _ => Value::Quote(Rc::new(gen_value(rng, depth - 1, in_backquote))),
+        }
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2000 {
+            let v = gen_value(&mut rng, 4, 0);
+            let printed = v.to_string();
+            let read = read(&printed);
+            assert_eq!(1, read.len(), "{}", printed);
+            assert_eq!(v, *read[0], "{}", printed);
+        }
+    }
+}